@@ -1,43 +1,76 @@
 use aoc2021::read_lines_from;
 
+use std::collections::VecDeque;
+
 pub fn main() {
     println!("Part 1: {}", part1());
     println!("Part 2: {}", part2());
 }
 
 pub fn part1() -> usize {
-    find_increases(
-        &read_lines_from("input/day1.txt")
-            .unwrap()
-            .map(|l| l.unwrap().parse::<u64>().expect("not a number"))
-            .collect::<Vec<u64>>(),
-    )
+    depths("input/day1.txt").count_increases()
 }
 
 pub fn part2() -> usize {
-    find_increases(&group_lines(
-        &read_lines_from("input/day1.txt")
-            .unwrap()
-            .map(|l| l.unwrap().parse::<u64>().expect("not a number"))
-            .collect::<Vec<u64>>(),
-    ))
+    depths("input/day1.txt").windowed_sums(3).count_increases()
 }
 
-fn find_increases(m: &[u64]) -> usize {
-    m.windows(2)
-        .map(|w| match w {
-            &[] => false,
-            &[_] => false,
-            [a, b, ..] => b > a,
-        })
-        .filter(|&a| a)
-        .count()
+/// Stream depth readings from a sonar log, one per line
+fn depths(input_fn: &str) -> impl Iterator<Item = u64> {
+    read_lines_from(input_fn)
+        .unwrap()
+        .map(|l| l.unwrap().parse::<u64>().expect("not a number"))
+}
+
+/// Sums of every run of `n` consecutive readings, holding on to only `n` readings at a time
+struct WindowedSums<I> {
+    inner: I,
+    n: usize,
+    window: VecDeque<u64>,
+    sum: u64,
+}
+
+impl<I: Iterator<Item = u64>> Iterator for WindowedSums<I> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // drop the oldest reading once the window is full
+        if self.window.len() == self.n {
+            self.sum -= self.window.pop_front().unwrap();
+        }
+        while self.window.len() < self.n {
+            let d = self.inner.next()?;
+            self.sum += d;
+            self.window.push_back(d);
+        }
+        Some(self.sum)
+    }
 }
 
-fn group_lines(m: &[u64]) -> Vec<u64> {
-    m.windows(3).map(|w| w.iter().sum()).collect()
+trait SonarEx: Iterator<Item = u64> + Sized {
+    /// Sum each window of `n` consecutive readings
+    fn windowed_sums(self, n: usize) -> WindowedSums<Self> {
+        assert!(n > 0, "windows must hold at least one reading");
+        WindowedSums {
+            inner: self,
+            n,
+            window: VecDeque::with_capacity(n),
+            sum: 0,
+        }
+    }
+
+    /// Count how many readings are larger than the one before
+    fn count_increases(self) -> usize {
+        self.fold((0, None), |(count, prev), d| match prev {
+            Some(p) if d > p => (count + 1, Some(d)),
+            _ => (count, Some(d)),
+        })
+        .0
+    }
 }
 
+impl<I: Iterator<Item = u64>> SonarEx for I {}
+
 #[cfg(test)]
 mod day1_tests {
 
@@ -54,21 +87,43 @@ mod day1_tests {
           260\n\
           263";
 
-    #[test]
-    fn test_find_increases() {
-        let test_data = TEST_DATA_STR
+    fn test_data() -> impl Iterator<Item = u64> {
+        TEST_DATA_STR
             .lines()
             .map(|l| l.parse::<u64>().expect("not a number"))
-            .collect::<Vec<u64>>();
-        assert_eq!(find_increases(&test_data), 7);
+    }
+
+    #[test]
+    fn test_find_increases() {
+        assert_eq!(test_data().count_increases(), 7);
     }
 
     #[test]
     fn test_find_increases_multi() {
-        let test_data = TEST_DATA_STR
-            .lines()
-            .map(|l| l.parse::<u64>().expect("not a number"))
-            .collect::<Vec<u64>>();
-        assert_eq!(find_increases(&group_lines(&test_data)), 5);
+        assert_eq!(test_data().windowed_sums(3).count_increases(), 5);
+    }
+
+    #[test]
+    fn test_windowed_sums() {
+        assert_eq!(
+            vec![607, 618, 618, 617, 647, 716, 769, 792],
+            test_data().windowed_sums(3).collect::<Vec<u64>>()
+        );
+        assert_eq!(
+            test_data().collect::<Vec<u64>>(),
+            test_data().windowed_sums(1).collect::<Vec<u64>>()
+        );
+        assert_eq!(
+            vec![2256],
+            test_data().windowed_sums(10).collect::<Vec<u64>>()
+        );
+        assert_eq!(0, test_data().windowed_sums(11).count());
+    }
+
+    #[test]
+    fn test_infinite_stream() {
+        // nothing gets buffered beyond the window, so an endless log is fine
+        let sums = (1..).windowed_sums(4).take(3).collect::<Vec<u64>>();
+        assert_eq!(vec![10, 14, 18], sums);
     }
 }
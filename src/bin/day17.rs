@@ -6,61 +6,147 @@ use aoc2021::lines_as_vec;
  */
 
 fn main() {
+    // `day17 <v_x> <v_y>` plots a single shot at the target instead
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let [v_x, v_y] = args.as_slice() {
+        let tgt = parse_line(&lines_as_vec("input/day17.txt")[0]);
+        let shot = fire(&tgt, (v_x.parse().unwrap(), v_y.parse().unwrap()));
+        print!("{}", ShotPrinter(&tgt, &shot.path));
+        println!("{:?}", shot.outcome);
+        return;
+    }
+
     println!("{}", part1());
     println!("{}", part2());
 }
 
 fn part1() -> i32 {
     let tgt = parse_line(&lines_as_vec("input/day17.txt")[0]);
-    ((tgt.b_y.abs() - 1) * tgt.b_y.abs()) / 2
+    solve(&tgt).highest.map_or(0, |(_, h)| h)
 }
 
-fn part2() -> i32 {
+fn part2() -> usize {
     let tgt = parse_line(&lines_as_vec("input/day17.txt")[0]);
-    let mut solns = 0;
-    println!("{:?}", tgt);
-    for v_x in 0..=tgt.r_x {
-        for v_y in tgt.b_y..tgt.b_y.abs() {
-            let mut v_x = v_x;
-            let mut v_y = v_y;
-            let mut x = 0;
-            let mut y = 0;
-            for _ in 0..3000 {
-                x += v_x;
-                y += v_y;
-                v_x = if v_x > 0 { v_x - 1 } else { 0 };
-                v_y -= 1;
-                if x >= tgt.l_x && x <= tgt.r_x && y <= tgt.t_y && y >= tgt.b_y {
-                    solns += 1;
-                    break;
-                }
+    solve(&tgt).velocities.len()
+}
+
+/// Every initial velocity that lands a probe in the target, and the one that goes highest
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Solution {
+    /// All valid initial velocities, as `(v_x, v_y)`
+    velocities: Vec<(i32, i32)>,
+    /// The valid velocity with the highest apex, and that apex
+    highest: Option<((i32, i32), i32)>,
+}
+
+/// The steps at which a probe is within the target along a single axis
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Hits {
+    /// Individual steps at which the probe is in range
+    steps: Vec<u32>,
+    /// The probe stops moving in range, and stays there from this step on
+    from: Option<u32>,
+}
+
+impl Hits {
+    fn contains(&self, t: u32) -> bool {
+        self.from.is_some_and(|f| t >= f) || self.steps.contains(&t)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.from.is_none()
+    }
+}
+
+/// Find the steps at which a probe launched with `v_x` is between `l_x` and `r_x`
+///
+/// Drag pulls the velocity toward zero by one each step, so once it reaches zero the probe stays
+/// put forever.
+fn x_hits(v_x: i32, l_x: i32, r_x: i32) -> Hits {
+    let mut hits = Hits::default();
+    let (mut x, mut v) = (0, v_x);
+    for t in 1.. {
+        x += v;
+        v -= v.signum();
+        let inside = x >= l_x && x <= r_x;
+        if v == 0 {
+            if inside {
+                hits.from = Some(t);
             }
+            break;
+        }
+        if inside {
+            hits.steps.push(t);
+        }
+        // moving away from the target and already past it
+        if (v > 0 && x > r_x) || (v < 0 && x < l_x) {
+            break;
         }
     }
-    solns
+    hits
 }
 
-fn _solve_tri(n: i32) -> Option<i32> {
-    let s = f64::sqrt((2.0 * f64::from(n)) + 0.25) - 0.5;
-    if s.fract() == 0.0 {
-        Some(unsafe { f64::to_int_unchecked::<i32>(s) })
-    } else {
-        None
+/// Find the steps at which a probe launched with `v_y` is between `b_y` and `t_y`
+///
+/// Gravity always wins eventually, so this is a finite set of steps.
+fn y_hits(v_y: i32, b_y: i32, t_y: i32) -> Hits {
+    let mut hits = Hits::default();
+    let (mut y, mut v) = (0, v_y);
+    for t in 1.. {
+        y += v;
+        v -= 1;
+        if y >= b_y && y <= t_y {
+            hits.steps.push(t);
+        }
+        // falling and already below the target
+        if v < 0 && y < b_y {
+            break;
+        }
     }
+    hits
 }
 
-fn _find_lowest_tri(from: i32, to: i32) -> Option<i32> {
-    let mut i = 0;
-    loop {
-        i += 1;
-        let r = i * (i + 1) / 2;
-        if r > from {
-            break Some(i);
-        }
-        if r > to {
-            break None;
+/// Find every initial velocity that puts the probe inside `tgt` after some whole number of steps
+///
+/// Each axis is independent, so we work out the steps at which each candidate `v_y` is in range,
+/// then keep any `v_x` whose in-range steps share at least one of them.
+fn solve(tgt: &Target) -> Solution {
+    // Any faster and the probe skips over the target on the first step (or, for y, on the way
+    // back down through zero).
+    let x_range = tgt.l_x.min(0)..=tgt.r_x.max(0);
+    let y_range = tgt.b_y.min(0)..=tgt.b_y.abs().max(tgt.t_y.abs());
+
+    let xs = x_range
+        .map(|v_x| (v_x, x_hits(v_x, tgt.l_x, tgt.r_x)))
+        .filter(|(_, h)| !h.is_empty())
+        .collect::<Vec<_>>();
+    let ys = y_range
+        .map(|v_y| (v_y, y_hits(v_y, tgt.b_y, tgt.t_y)))
+        .filter(|(_, h)| !h.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut soln = Solution::default();
+    for (v_x, x_h) in xs.iter() {
+        for (v_y, y_h) in ys.iter() {
+            if y_h.steps.iter().any(|&t| x_h.contains(t)) {
+                soln.velocities.push((*v_x, *v_y));
+                let apex = apex(*v_y);
+                if soln.highest.is_none_or(|(_, h)| apex > h) {
+                    soln.highest = Some(((*v_x, *v_y), apex));
+                }
+            }
         }
     }
+    soln
+}
+
+/// The highest point reached by a probe launched with `v_y`
+fn apex(v_y: i32) -> i32 {
+    if v_y > 0 {
+        v_y * (v_y + 1) / 2
+    } else {
+        0
+    }
 }
 
 fn parse_line(line: &str) -> Target {
@@ -73,6 +159,125 @@ fn parse_line(line: &str) -> Target {
     Target { l_x, r_x, b_y, t_y }
 }
 
+/// A probe in flight, yielding its position after each step
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Probe {
+    x: i32,
+    y: i32,
+    v_x: i32,
+    v_y: i32,
+}
+
+impl Probe {
+    fn new(v_x: i32, v_y: i32) -> Self {
+        Probe {
+            x: 0,
+            y: 0,
+            v_x,
+            v_y,
+        }
+    }
+}
+
+impl Iterator for Probe {
+    type Item = (i32, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.x += self.v_x;
+        self.y += self.v_y;
+        self.v_x -= self.v_x.signum();
+        self.v_y -= 1;
+        Some((self.x, self.y))
+    }
+}
+
+/// How a shot ended, and the step at which that was decided
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+    /// The probe was inside the target after this step
+    Hit(u32),
+    /// The probe went past (or dropped through) the target and can no longer reach it
+    Overshoot(u32),
+    /// The probe ran out of horizontal speed, or fell, before reaching the target
+    Undershoot(u32),
+}
+
+/// The path a probe took, up to and including the step that decided the outcome
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Shot {
+    path: Vec<(i32, i32)>,
+    outcome: Outcome,
+}
+
+/// Launch a probe at `tgt` and follow it until it either hits or cannot
+fn fire(tgt: &Target, (v_x, v_y): (i32, i32)) -> Shot {
+    let mut probe = Probe::new(v_x, v_y);
+    let mut path = Vec::new();
+    for t in 1.. {
+        let (x, y) = probe.next().unwrap();
+        path.push((x, y));
+
+        let outcome = if tgt.contains(x, y) {
+            Some(Outcome::Hit(t))
+        } else if (x > tgt.r_x && probe.v_x >= 0) || (x < tgt.l_x && probe.v_x <= 0) {
+            // off to the side, and never coming back
+            Some(if tgt.short_of(x) {
+                Outcome::Undershoot(t)
+            } else {
+                Outcome::Overshoot(t)
+            })
+        } else if y < tgt.b_y && probe.v_y < 0 {
+            // below the target, and still falling
+            Some(if tgt.short_of(x) {
+                Outcome::Undershoot(t)
+            } else {
+                Outcome::Overshoot(t)
+            })
+        } else {
+            None
+        };
+
+        if let Some(outcome) = outcome {
+            return Shot { path, outcome };
+        }
+    }
+    unreachable!()
+}
+
+/// Draws a shot the way the puzzle does: `S` for the launcher, `#` for the probe, `T` for the
+/// target area
+struct ShotPrinter<'a>(&'a Target, &'a [(i32, i32)]);
+
+impl std::fmt::Display for ShotPrinter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ShotPrinter(tgt, path) = self;
+        let (min_x, max_x, min_y, max_y) = path.iter().fold(
+            (
+                tgt.l_x.min(0),
+                tgt.r_x.max(0),
+                tgt.b_y.min(0),
+                tgt.t_y.max(0),
+            ),
+            |acc, &(x, y)| (acc.0.min(x), acc.1.max(x), acc.2.min(y), acc.3.max(y)),
+        );
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                if (x, y) == (0, 0) {
+                    write!(f, "S")?;
+                } else if path.contains(&(x, y)) {
+                    write!(f, "#")?;
+                } else if tgt.contains(x, y) {
+                    write!(f, "T")?;
+                } else {
+                    write!(f, ".")?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct Target {
     l_x: i32,
@@ -81,7 +286,17 @@ struct Target {
     t_y: i32,
 }
 
-/*
+impl Target {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.l_x && x <= self.r_x && y >= self.b_y && y <= self.t_y
+    }
+
+    /// Whether `x` lies between the launcher and the target
+    fn short_of(&self, x: i32) -> bool {
+        (self.l_x > 0 && x < self.l_x) || (self.r_x < 0 && x > self.r_x)
+    }
+}
+
 #[cfg(test)]
 mod day17_tests {
 
@@ -90,45 +305,102 @@ mod day17_tests {
     const TEST_DATA: &str = "target area: x=20..30, y=-10..-5";
 
     #[test]
-    fn test_part2() {
-        let tgt = parse_line(TEST_DATA);
-
-        // probes fired upward fall back down with v_neg @ h_0 = -v_0-1
-        let up_y_velocities: Vec<i32> = (0..=(tgt.b_y.abs() - 1)).collect();
-        println!("{:?}", up_y_velocities);
-
-        // probes fired downward so they just hit the target range immediately
-        let direct_y_velocities: Vec<i32> = (tgt.b_y..=tgt.t_y).collect();
-        println!("{:?}", direct_y_velocities);
-
-        // probes fired downward with increasing velocity until they miss
-        //let accelerating_y_velocities: Vec<i32> = (0..=
-        println!("adsf {}", find_lowest_tri(tgt.b_y, tgt.t_y).unwrap());
-        let dropped_y_velocities: Vec<i32> = (find_lowest_tri(tgt.b_y, tgt.t_y).unwrap()
-            ..find_lowest_tri(tgt.b_y - 100, tgt.b_y).unwrap())
-            .collect();
-        println!("{:?}", dropped_y_velocities);
-
-        let slowing_x_velocities: Vec<i32> =
-            ((find_lowest_tri(tgt.l_x, tgt.r_x).unwrap())..=(tgt.r_x / 2)).collect();
-        println!("{:?}", slowing_x_velocities);
+    fn test_hits() {
+        // settles at x=21 after six steps
+        assert_eq!(
+            Hits {
+                steps: vec![5],
+                from: Some(6)
+            },
+            x_hits(6, 20, 30)
+        );
+        assert_eq!(
+            Hits {
+                steps: vec![1],
+                from: None
+            },
+            x_hits(25, 20, 30)
+        );
+        assert!(x_hits(31, 20, 30).is_empty());
+        assert_eq!(vec![20], y_hits(9, -10, -5).steps);
+        assert!(y_hits(10, -10, -5).is_empty());
+    }
 
-        let direct_x_velocities: Vec<i32> = (tgt.l_x..=tgt.r_x).collect();
-        println!("{:?}", direct_x_velocities);
+    #[test]
+    fn test_part1() {
+        let tgt = parse_line(TEST_DATA);
+        assert_eq!(Some(((6, 9), 45)), solve(&tgt).highest);
+    }
 
-        assert!(false);
+    #[test]
+    fn test_part2() {
+        let tgt = parse_line(TEST_DATA);
+        let soln = solve(&tgt);
+        assert_eq!(112, soln.velocities.len());
+        assert!(soln.velocities.contains(&(7, -1)));
+        assert!(soln.velocities.contains(&(30, -10)));
     }
 
     #[test]
-    fn test_find_tris() {
-        assert_eq!(Some(6), find_lowest_tri(20, 21));
+    fn test_fire() {
+        let tgt = parse_line(TEST_DATA);
+        assert_eq!(Outcome::Hit(7), fire(&tgt, (7, 2)).outcome);
+        assert_eq!(Outcome::Hit(9), fire(&tgt, (6, 3)).outcome);
+        assert_eq!(Outcome::Hit(4), fire(&tgt, (9, 0)).outcome);
+        assert_eq!(Outcome::Overshoot(2), fire(&tgt, (17, -4)).outcome);
+        assert_eq!(Outcome::Undershoot(2), fire(&tgt, (2, -1)).outcome);
+        // the simulator agrees with the solver about every velocity it found
+        for &v in solve(&tgt).velocities.iter() {
+            assert!(matches!(fire(&tgt, v).outcome, Outcome::Hit(_)));
+        }
     }
 
     #[test]
-    fn test_part1() {
+    fn test_plot() {
         let tgt = parse_line(TEST_DATA);
+        let shot = fire(&tgt, (7, 2));
+        assert_eq!(
+            "\
+.............#....#............
+.......#..............#........
+...............................
+S........................#.....
+...............................
+...............................
+...........................#...
+...............................
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTT#TT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+",
+            format!("{}", ShotPrinter(&tgt, &shot.path))
+        );
+    }
 
-        assert_eq!(45, ((tgt.b_y.abs() - 1) * tgt.b_y.abs()) / 2);
+    #[test]
+    fn test_target_above() {
+        let tgt = parse_line("target area: x=20..30, y=5..10");
+        let soln = solve(&tgt);
+        // straight in on the first step
+        assert!(soln.velocities.contains(&(25, 7)));
+        assert!(soln
+            .velocities
+            .iter()
+            .all(|&(_, v_y)| (0..=10).contains(&v_y)));
+        assert_eq!(Some(((6, 10), 55)), soln.highest);
+    }
+
+    #[test]
+    fn test_target_straddles_zero() {
+        let tgt = parse_line("target area: x=-2..2, y=-10..-5");
+        let soln = solve(&tgt);
+        // dropped straight down, it stays over the target until it falls through it
+        assert!(soln.velocities.contains(&(0, 0)));
+        assert!(soln.velocities.contains(&(-2, -5)));
+        assert!(soln.velocities.contains(&(0, 9)));
+        assert!(!soln.velocities.contains(&(3, -5)));
     }
 }
-*/
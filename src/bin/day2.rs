@@ -1,46 +1,46 @@
 use aoc2021::read_lines_from;
-use aoc2021::sub::{Nav, NavParseError, Sub, SubError};
+use aoc2021::sub::{parse_navs, Course, Nav, NavModel, Sub, SubError};
 
 fn main() {
-    println!("Solution: {}", part1());
-}
-
-fn part1() -> u64 {
-    let mut sub: Sub = Sub::default();
-
     let lines = &read_lines_from("input/day2.txt")
         .unwrap() // die if we can't read the file
         .collect::<Result<Vec<String>, std::io::Error>>()
         .unwrap();
 
-    let navs = lines_to_navs(lines).unwrap(); // die if there's a bad line
+    let navs = parse_navs(lines).unwrap_or_else(|e| panic!("{}", e)); // die if there's a bad line
 
-    navigate_sub(&mut sub, navs).unwrap();
+    // `day2 csv` dumps the part 2 course instead
+    if std::env::args().nth(1).as_deref() == Some("csv") {
+        let mut course = Course::new(Sub::new(NavModel::Aim));
+        course.try_moves(&navs).unwrap();
+        print!("{}", course.to_csv());
+        return;
+    }
 
-    sub.dist * sub.depth
+    println!("Part 1: {}", solve(&navs, NavModel::Direct));
+    println!("Part 2: {}", solve(&navs, NavModel::Aim));
 }
 
-fn lines_to_navs(lines: &[String]) -> Result<Vec<Nav>, NavParseError> {
-    lines
-        .iter()
-        .map(|l| Nav::try_from(l.as_str()))
-        .collect::<Result<Vec<Nav>, NavParseError>>()
+fn solve(navs: &[Nav], model: NavModel) -> u64 {
+    let mut sub = Sub::new(model);
+
+    navigate_sub(&mut sub, navs).unwrap();
+
+    // subs cannot fly by default, so depth is never negative
+    sub.dist * u64::try_from(sub.depth).unwrap()
 }
 
-fn navigate_sub(sub: &mut Sub, moves: Vec<Nav>) -> Result<(), SubError> {
-    moves
-        .into_iter()
-        .try_for_each(|nav| sub.try_move(nav))
+fn navigate_sub(sub: &mut Sub, moves: &[Nav]) -> Result<(), SubError> {
+    moves.iter().try_for_each(|&nav| sub.try_move(nav))
 }
 
 #[cfg(test)]
 mod day2_tests {
 
-    use aoc2021::sub::Nav;
     use super::*;
+    use aoc2021::sub::{Nav, Surface};
 
-    const TEST_DATA_STR: &str =
-"forward 5
+    const TEST_DATA_STR: &str = "forward 5
 down 5
 forward 8
 up 3
@@ -49,32 +49,49 @@ forward 2";
 
     #[test]
     fn test_nav_gen() {
-        let test_data: Vec<String> = TEST_DATA_STR
-            .lines()
-            .map(|l| l.to_string())
-            .collect();
+        let test_data: Vec<String> = TEST_DATA_STR.lines().map(|l| l.to_string()).collect();
 
         assert_eq!(
-            Ok(vec![Nav::Fore(5), Nav::Down(5), Nav::Fore(8), Nav::Up(3), Nav::Down(8), Nav::Fore(2)]),
-            lines_to_navs(&test_data)
+            Ok(vec![
+                Nav::Fore(5),
+                Nav::Down(5),
+                Nav::Fore(8),
+                Nav::Up(3),
+                Nav::Down(8),
+                Nav::Fore(2)
+            ]),
+            parse_navs(&test_data)
         )
     }
 
     #[test]
     fn test_move_sub() {
-        let test_data: Vec<Nav> = lines_to_navs(
+        let test_data: Vec<Nav> = parse_navs(
             TEST_DATA_STR
                 .lines()
                 .map(|l| l.to_string())
                 .collect::<Vec<String>>()
-                .as_slice()
-        ).unwrap();
+                .as_slice(),
+        )
+        .unwrap();
 
         let mut s = Sub::default();
-        let nav_result = navigate_sub(&mut s, test_data);
+        let nav_result = navigate_sub(&mut s, &test_data);
 
         assert_eq!(nav_result, Ok(()));
-        assert_eq!(s, Sub{dist: 15, depth: 60, aim: 10});
+        assert_eq!(
+            s,
+            Sub {
+                dist: 15,
+                depth: 60,
+                aim: 10,
+                model: NavModel::Aim,
+                surface: Surface::Error
+            }
+        );
+
+        let mut s = Sub::new(NavModel::Direct);
+        navigate_sub(&mut s, &test_data).unwrap();
+        assert_eq!((15, 10), (s.dist, s.depth));
     }
-
 }
@@ -1,77 +1,169 @@
 use aoc2021::lines_as_vec;
 
-use std::collections::VecDeque;
+use bit_vec::BitVec;
 
 fn main() {
+    // `day20 pbm <dir> <steps>` writes the image before and after each of <steps> enhancements
+    // to <dir>, instead of solving the puzzle
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(cmd) = args.first().filter(|&c| c == "pbm") {
+        let (dir, steps) = match &args[1..] {
+            [dir, steps] => (dir, steps.parse::<usize>().expect("steps must be a number")),
+            _ => panic!("usage: day20 {} <dir> <steps>", cmd),
+        };
+        let lines = lines_as_vec("input/day20.txt");
+        let (e, i) = parse(&lines);
+        for (n, i) in std::iter::once(i.clone())
+            .chain(i.enhancements(&e))
+            .take(steps + 1)
+            .enumerate()
+        {
+            let path = format!("{}/day20_{:03}.pbm", dir, n);
+            if let Err(err) = std::fs::write(&path, i.to_pbm()) {
+                eprintln!("couldn't write {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     println!("Part 1: {}", part1());
+    println!("Part 2: {}", part2());
 }
 
 fn part1() -> usize {
     let lines = lines_as_vec("input/day20.txt");
-    let (e, mut i) = parse(&lines);
-
-    expand(&mut i);
-    let mut i = enhance(i, &e);
-
-    expand(&mut i);
-    let i = enhance(i, &e);
+    let (e, i) = parse(&lines);
+    i.enhance_n(&e, 2).lit().unwrap()
+}
 
-    i.iter().flatten().filter(|&b| *b == b'#').count()
+fn part2() -> usize {
+    let lines = lines_as_vec("input/day20.txt");
+    let (e, i) = parse(&lines);
+    i.enhance_n(&e, 50).lit().unwrap()
 }
 
-fn parse(lines: &[String]) -> (Vec<u8>, VecDeque<Vec<u8>>) {
-    let enhancer: Vec<u8> = lines[0].clone().as_bytes().into_iter().cloned().collect();
+fn parse(lines: &[String]) -> (Vec<bool>, Image) {
+    let enhancer: Vec<bool> = lines[0].bytes().map(|b| b == b'#').collect();
+    assert_eq!(
+        512,
+        enhancer.len(),
+        "enhancement string must cover every 9-bit window"
+    );
 
-    let img: VecDeque<Vec<u8>> = lines[2..]
-        .into_iter()
-        .map(|l| l.as_bytes().into_iter().cloned().collect())
+    let rows: Vec<BitVec> = lines[2..]
+        .iter()
+        .map(|l| l.bytes().map(|b| b == b'#').collect())
         .collect();
 
-    (enhancer, img)
+    (
+        enhancer,
+        Image {
+            width: rows.first().map_or(0, BitVec::len),
+            rows,
+            background: false,
+        },
+    )
 }
 
-fn expand(s: &mut VecDeque<Vec<u8>>) {
-    // every iteration, we increase the height and width by no more than 4
-    let width = s[0].len() + 4;
-    s.iter_mut().for_each(|l| {
-        l.insert(0, b'.');
-        l.insert(0, b'.');
-        l.push(b'.');
-        l.push(b'.');
-    });
-    s.push_front(Vec::from_iter(vec![b'.'; width]));
-    s.push_front(Vec::from_iter(vec![b'.'; width]));
-    s.push_back(Vec::from_iter(vec![b'.'; width]));
-    s.push_back(Vec::from_iter(vec![b'.'; width]));
+/// An infinite image: a finite region we keep track of, and the one value shared by every pixel
+/// outside it
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Image {
+    /// The part of the image that may differ from the background, one bit per pixel
+    rows: Vec<BitVec>,
+    width: usize,
+    /// The value of every pixel outside `rows`
+    background: bool,
 }
 
-fn str_to_idx(s: &[u8]) -> usize {
-    s.iter().rev().enumerate().fold(
-        0,
-        |acc, (i, &c)| if c == b'#' { acc | (1 << i) } else { acc },
-    )
-}
+/// The bits of a window that stay put when it slides one pixel to the right
+const KEEP: usize = 0b110_110_110;
+
+impl Image {
+    fn row(&self, y: isize) -> Option<&BitVec> {
+        usize::try_from(y).ok().and_then(|y| self.rows.get(y))
+    }
+
+    fn bit(&self, row: Option<&BitVec>, x: usize) -> bool {
+        row.and_then(|r| r.get(x)).unwrap_or(self.background)
+    }
+
+    fn enhance(&self, enhancer: &[bool]) -> Image {
+        let height = self.rows.len() as isize;
+        let width = self.width;
 
-fn enhance(s: VecDeque<Vec<u8>>, enhancer: &[u8]) -> VecDeque<Vec<u8>> {
-    let mut out = s.clone();
-    for y in 1..(s.len() - 1) {
-        for x in 1..(s[0].len() - 1) {
-            let bitstr: Vec<u8> = [
-                Vec::from(&s[y - 1][(x - 1)..=(x + 1)]),
-                Vec::from(&s[y][(x - 1)..=(x + 1)]),
-                Vec::from(&s[y + 1][(x - 1)..=(x + 1)]),
-            ]
-            .into_iter()
-            .flatten()
+        // Every output pixel only looks at its neighbours, so the part of the image that can
+        // differ from the background grows by one on each side.
+        let rows = (-1..=height)
+            .map(|y| {
+                let [above, row, below] = [y - 1, y, y + 1].map(|y| self.row(y));
+
+                // Slide a 9-bit window along the three source rows: every step, the left column
+                // drops off and the next column to the right comes in.  It starts out with the
+                // two columns left of the image, which are background.
+                let mut idx = if self.background { 0x1ff } else { 0 };
+                (0..width + 2)
+                    .map(|x| {
+                        idx = ((idx << 1) & KEEP)
+                            | usize::from(self.bit(above, x)) << 6
+                            | usize::from(self.bit(row, x)) << 3
+                            | usize::from(self.bit(below, x));
+                        enhancer[idx]
+                    })
+                    .collect()
+            })
             .collect();
-            let idx = str_to_idx(&bitstr);
-            out[y][x] = enhancer[idx];
+
+        // Out in the background, every window is either all dark or all lit.  If the enhancer
+        // lights an all-dark window, the whole infinite background flips.
+        let background = enhancer[if self.background { 511 } else { 0 }];
+
+        Image {
+            rows,
+            width: self.width + 2,
+            background,
         }
     }
-    out
-}
 
-/*
+    /// Every image that comes out of enhancing this one over and over
+    fn enhancements<'e>(&self, enhancer: &'e [bool]) -> impl Iterator<Item = Image> + 'e {
+        std::iter::successors(Some(self.enhance(enhancer)), move |i| {
+            Some(i.enhance(enhancer))
+        })
+    }
+
+    fn enhance_n(&self, enhancer: &[bool], steps: usize) -> Image {
+        match steps {
+            0 => self.clone(),
+            n => self.enhancements(enhancer).nth(n - 1).unwrap(),
+        }
+    }
+
+    /// Count the lit pixels, or `None` if there are infinitely many
+    fn lit(&self) -> Option<usize> {
+        if self.background {
+            None
+        } else {
+            Some(
+                self.rows
+                    .iter()
+                    .map(|r| r.iter().filter(|&p| p).count())
+                    .sum(),
+            )
+        }
+    }
+
+    /// Encode the tracked region as a binary PBM, with lit pixels black
+    fn to_pbm(&self) -> Vec<u8> {
+        // PBM rows are packed MSB-first and padded to a whole byte, just like `BitVec`'s
+        let mut pbm = format!("P4\n{} {}\n", self.width, self.rows.len()).into_bytes();
+        for row in self.rows.iter() {
+            pbm.extend(row.to_bytes());
+        }
+        pbm
+    }
+}
 
 #[cfg(test)]
 mod day20_tests {
@@ -80,56 +172,23 @@ mod day20_tests {
 
     use super::*;
 
-    #[test]
-    fn test_enhance() {
-        let mut v = VecDeque::from_iter([
-            Vec::from_iter(String::from("...").into_bytes()),
-            Vec::from_iter(String::from("...").into_bytes()),
-            Vec::from_iter(String::from("#.#").into_bytes()),
-        ]);
-        let e = Vec::from_iter(String::from("....#").into_bytes());
-        let v = enhance(v, &e);
-        assert_eq!(
-            VecDeque::from_iter([
-                Vec::from_iter(String::from("...").into_bytes()),
-                Vec::from_iter(String::from("...").into_bytes()),
-                Vec::from_iter(String::from("#.#").into_bytes()),
-            ]),
-            v
-        );
-    }
-
-    #[test]
-    fn test_expand() {
-        let mut v = VecDeque::from_iter([
-            Vec::from_iter(String::from("...").into_bytes()),
-            Vec::from_iter(String::from("...").into_bytes()),
-            Vec::from_iter(String::from("...").into_bytes()),
-        ]);
-        expand(&mut v);
-        assert_eq!(
-            VecDeque::from_iter([
-                Vec::from_iter(String::from(".......").into_bytes()),
-                Vec::from_iter(String::from(".......").into_bytes()),
-                Vec::from_iter(String::from(".......").into_bytes()),
-                Vec::from_iter(String::from(".......").into_bytes()),
-                Vec::from_iter(String::from(".......").into_bytes()),
-                Vec::from_iter(String::from(".......").into_bytes()),
-                Vec::from_iter(String::from(".......").into_bytes()),
-            ]),
-            v
-        );
+    fn get(i: &Image, x: isize, y: isize) -> bool {
+        match usize::try_from(x) {
+            Ok(x) => i.bit(i.row(y), x),
+            Err(_) => i.background,
+        }
     }
 
-    #[test]
-    fn test_str_to_idx() {
-        assert_eq!(2, str_to_idx(".......#.".as_bytes()));
-        assert_eq!(2usize.pow(9) - 1, str_to_idx("#########".as_bytes()));
+    /// Read the 3x3 square centred on `(x, y)` as a 9-bit number, top left first
+    fn window(i: &Image, x: isize, y: isize) -> usize {
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .fold(0, |acc, (dx, dy)| {
+                (acc << 1) | usize::from(get(i, x + dx, y + dy))
+            })
     }
 
-    #[test]
-    fn test_case() {
-        let test_data = "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..###..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###.######.###.####...#.##.##..#..#..#####.....#.#....###..#.##......#.....#..#..#..##..#...##.######.####.####.#.#...#.......#..#.#.#...####.##.#......#..#...##.#.##..#...##.#.##..###.#......#.#.......#.#.#.####.###.##...#.....####.#..#..#.##.#....##..#.####....##...##..#...#......#.#.......#.......##..####..#...#.#.#...##..#.#..###..#####........#..####......#..#
+    const TEST_DATA: &str = "..#.#..#####.#.#.#.###.##.....###.##.#..###.####..#####..#....#..#..##..###..######.###...####..#..#####..##..#.#####...##.#.#..#.##..#.#......#.###.######.###.####...#.##.##..#..#..#####.....#.#....###..#.##......#.....#..#..#..##..#...##.######.####.####.#.#...#.......#..#.#.#...####.##.#......#..#...##.#.##..#...##.#.##..###.#......#.#.......#.#.#.####.###.##...#.....####.#..#..#.##.#....##..#.####....##...##..#...#......#.#.......#.......##..####..#...#.#.#...##..#.#..###..#####........#..####......#..#
 
 #..#.
 #....
@@ -137,28 +196,73 @@ mod day20_tests {
 ..#..
 ..###";
 
-        let (e, mut i) = parse(&str_as_vec(test_data));
-        // print_i(&i);
+    #[test]
+    fn test_window() {
+        let (_, i) = parse(&str_as_vec(TEST_DATA));
+        // ...#...#. in the puzzle's example
+        assert_eq!(34, window(&i, 2, 2));
+        assert_eq!(0, window(&i, -5, -5));
+    }
+
+    #[test]
+    fn test_case() {
+        let (e, i) = parse(&str_as_vec(TEST_DATA));
 
-        expand(&mut i);
-        let mut i = enhance(i, &e);
-        // print_i(&i);
+        let i = i.enhance_n(&e, 2);
+        assert_eq!(Some(35), i.lit());
 
-        expand(&mut i);
-        let mut i = enhance(i, &e);
-        // print_i(&i);
-        println!("{}", i.iter().flatten().filter(|&b| *b == b'#').count());
+        let i = i.enhance_n(&e, 48);
+        assert_eq!(Some(3351), i.lit());
+    }
 
-        assert!(false);
+    #[test]
+    fn test_sliding_window() {
+        // the sliding window has to agree with reading every window from scratch
+        let (e, i) = parse(&str_as_vec(TEST_DATA));
+        for i in i.enhancements(&e).take(3) {
+            let next = i.enhance(&e);
+            for y in -1..=(i.rows.len() as isize) {
+                for x in -1..=(i.width as isize) {
+                    assert_eq!(e[window(&i, x, y)], get(&next, x + 1, y + 1));
+                }
+            }
+        }
     }
-}
 
-/*
-fn print_i(i: &VecDeque<Vec<u8>>) {
-    for l in i {
-        println!("{}", String::from_utf8(l.to_vec()).unwrap());
+    #[test]
+    fn test_pbm() {
+        let i = Image {
+            rows: vec![
+                BitVec::from_fn(9, |x| x == 0 || x == 8),
+                BitVec::from_elem(9, false),
+            ],
+            width: 9,
+            background: false,
+        };
+        assert_eq!(
+            [&b"P4\n9 2\n"[..], &[0b1000_0000, 0b1000_0000, 0, 0]].concat(),
+            i.to_pbm()
+        );
     }
-}
-*/
 
-*/
+    #[test]
+    fn test_flipping_background() {
+        // an enhancer that inverts every pixel
+        let e = (0..512)
+            .map(|idx| idx & 0b10000 == 0)
+            .collect::<Vec<bool>>();
+        let i = Image {
+            rows: vec![BitVec::from_fn(2, |x| x == 0), BitVec::from_elem(2, false)],
+            width: 2,
+            background: false,
+        };
+
+        let i = i.enhance(&e);
+        assert!(i.background);
+        assert_eq!(None, i.lit());
+
+        let i = i.enhance(&e);
+        assert!(!i.background);
+        assert_eq!(Some(1), i.lit());
+    }
+}
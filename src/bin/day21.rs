@@ -1,85 +1,227 @@
+use aoc2021::lines_as_vec;
+use num_bigint::BigUint;
+
 use std::collections::HashMap;
 
 fn main() {
+    // `day21 csv` dumps the odds from every state of the real game instead
+    if std::env::args().nth(1).as_deref() == Some("csv") {
+        let starts = parse_starts(&lines_as_vec("input/day21.txt"));
+        let mut q = Quantum::new(&DIRAC);
+        q.odds(&State::new(&starts));
+        print!("{}", q.to_csv());
+        return;
+    }
+
     println!("{}", part1());
     println!("{}", part2());
 }
 
 fn part1() -> usize {
-    let (p1, p2, die) = play_part1(9, 6);
-    (die.0 - 1) * if p1.score >= 1000 { p2.score } else { p1.score }
+    let starts = parse_starts(&lines_as_vec("input/day21.txt"));
+    let (pawns, die) = play_part1(&starts, &PRACTICE);
+    die.rolled * pawns.iter().map(|p| p.score).min().unwrap()
+}
+
+/// The shape of a game of Dirac Dice
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Rules {
+    /// Number of spaces around the board
+    board_sz: usize,
+    /// Score at which a player wins
+    target: usize,
+    /// Number of faces on the die
+    faces: usize,
+    /// Number of times the die is rolled each turn
+    rolls: usize,
 }
 
+/// The practice game, played with a deterministic 100-sided die
+const PRACTICE: Rules = Rules {
+    board_sz: 10,
+    target: 1000,
+    faces: 100,
+    rolls: 3,
+};
+
+/// The real game, played with the Dirac die
+const DIRAC: Rules = Rules {
+    board_sz: 10,
+    target: 21,
+    faces: 3,
+    rolls: 3,
+};
+
+/// A point in the game: where everyone is, what they've scored, and whose turn it is
 #[derive(Eq, PartialEq, Clone, Debug, Hash)]
 struct State {
-    pos: Vec<u8>,
-    scores: Vec<u8>,
-    player: u8,
-    count: u64,
-}
-
-fn part2() -> u64 {
-    // Precompute counts of dice rolls.
-    let rolls = {
-        let mut histo = HashMap::new();
-        for i in 1..=3 {
-            for j in 1..=3 {
-                for k in 1..=3 {
-                    *histo.entry(i + j + k).or_default() += 1;
-                }
+    pos: Vec<usize>,
+    scores: Vec<usize>,
+    player: usize,
+}
+
+fn part2() -> BigUint {
+    let starts = parse_starts(&lines_as_vec("input/day21.txt"));
+    play_quantum(&starts, &DIRAC).into_iter().max().unwrap()
+}
+
+/// Count how many ways each total can come up when rolling a `faces`-sided die `rolls` times
+fn roll_counts(faces: usize, rolls: usize) -> Vec<(usize, u128)> {
+    let mut histo = HashMap::from([(0, 1)]);
+    for _ in 0..rolls {
+        let mut next = HashMap::new();
+        for (total, count) in histo {
+            for face in 1..=faces {
+                *next.entry(total + face).or_default() += count;
             }
         }
-        let mut rolls = histo
-            .iter()
-            .map(|(k, v)| (*k, *v))
-            .collect::<Vec<(usize, usize)>>();
-        rolls.sort();
-        rolls
-    };
-
-    // I expressed this as a game-tree search problem.
-    //
-    // We express the number of universes on the same "path" via the 'count' parameter.  Without
-    // this, the number grows too quickly.  E.g., when you roll 3 dice, there is one universe where
-    // you get 1+1+1, but there are 3 universes where you get 1+1+2.
-    let state = State {
-        pos: vec![9, 6],
-        scores: vec![0, 0],
-        player: 0,
-        count: 1,
-    };
-
-    // Since we need to evaluate the entire space, depth-first search is most efficient.
-    let mut queue = Vec::new();
-    queue.push(state);
-
-    let mut wins = vec![0, 0];
-
-    while queue.len() > 0 {
-        let next = queue.pop().unwrap();
-
-        // Enumerate eligible moves.
-        for (roll, newcounts) in rolls.iter() {
-            let mut newstate = next.clone();
-            newstate.pos[next.player as usize] =
-                ((newstate.pos[next.player as usize] - 1 + *roll as u8) % 10) + 1;
-            newstate.scores[next.player as usize] += newstate.pos[next.player as usize];
-            newstate.count *= *newcounts as u64;
-
-            // Count win conditions.
-            if newstate.scores[next.player as usize] >= 21 {
-                wins[next.player as usize] += newstate.count;
+        histo = next;
+    }
+    let mut rolls = histo.into_iter().collect::<Vec<(usize, u128)>>();
+    rolls.sort();
+    rolls
+}
+
+/// Play out every universe of a game starting from `starts`, returning the number won by each
+/// player
+fn play_quantum(starts: &[usize], rules: &Rules) -> Vec<BigUint> {
+    Quantum::new(rules).odds(&State::new(starts)).wins
+}
+
+impl State {
+    /// The state before anyone has moved
+    fn new(starts: &[usize]) -> Self {
+        State {
+            pos: starts.to_vec(),
+            scores: vec![0; starts.len()],
+            player: 0,
+        }
+    }
+}
+
+/// How the game turns out for each player from some state
+#[derive(Clone, Debug, PartialEq)]
+struct Odds {
+    /// Number of universes each player wins, which soon outgrows any fixed size integer as the
+    /// target goes up
+    wins: Vec<BigUint>,
+    /// Chance each player wins, with every roll equally likely
+    prob: Vec<f64>,
+}
+
+/// Works out the odds from any state of a Dirac Dice game, remembering every state it has seen
+struct Quantum {
+    rules: Rules,
+    /// Totals of a turn's rolls, and how many universes roll each one
+    rolls: Vec<(usize, u128)>,
+    cache: HashMap<State, Odds>,
+}
+
+impl Quantum {
+    fn new(rules: &Rules) -> Self {
+        Quantum {
+            rules: *rules,
+            rolls: roll_counts(rules.faces, rules.rolls),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Count the universes each player wins, and their chance of winning, starting from `state`
+    ///
+    /// The universes that follow from a state don't depend on how we got there, so each state is
+    /// only ever worked out once, however many paths lead to it.  E.g., when you roll 3 dice,
+    /// there is one universe where you get 1+1+1, but there are 3 universes where you get 1+1+2;
+    /// all of them land on the same state.
+    ///
+    /// Universe counts aren't probabilities: a game that ends sooner splits into fewer universes,
+    /// but is no less likely.
+    fn odds(&mut self, state: &State) -> Odds {
+        if let Some(odds) = self.cache.get(state) {
+            return odds.clone();
+        }
+
+        let player = state.player;
+        let turns = self.rolls.iter().map(|(_, c)| c).sum::<u128>() as f64;
+        let mut odds = Odds {
+            wins: vec![BigUint::default(); state.pos.len()],
+            prob: vec![0.0; state.pos.len()],
+        };
+        for i in 0..self.rolls.len() {
+            let (roll, count) = self.rolls[i];
+            let chance = count as f64 / turns;
+            let mut next = state.clone();
+            next.pos[player] = ((next.pos[player] - 1 + roll) % self.rules.board_sz) + 1;
+            next.scores[player] += next.pos[player];
+
+            if next.scores[player] >= self.rules.target {
+                odds.wins[player] += count;
+                odds.prob[player] += chance;
                 continue;
             }
 
-            newstate.player = (newstate.player + 1) % 2;
-            queue.push(newstate);
+            next.player = (player + 1) % state.pos.len();
+            let sub = self.odds(&next);
+            for (w, sub) in odds.wins.iter_mut().zip(sub.wins) {
+                *w += sub * count;
+            }
+            for (p, sub) in odds.prob.iter_mut().zip(sub.prob) {
+                *p += chance * sub;
+            }
         }
+
+        self.cache.insert(state.clone(), odds.clone());
+        odds
+    }
+
+    /// Every state worked out so far, with its odds, in order
+    fn table(&self) -> Vec<(&State, &Odds)> {
+        let mut table = self.cache.iter().collect::<Vec<_>>();
+        table.sort_by_key(|(s, _)| (s.player, &s.pos, &s.scores));
+        table
+    }
+
+    /// Dump `table` as CSV, one row per state, numbering players from 1 as the puzzle does
+    fn to_csv(&self) -> String {
+        let players = self.cache.keys().next().map_or(0, |s| s.pos.len());
+        let cols = |name: &str| {
+            (1..=players)
+                .map(|i| format!("{}_{}", name, i))
+                .collect::<Vec<_>>()
+        };
+        let mut csv = [
+            vec![String::from("player")],
+            cols("pos"),
+            cols("score"),
+            cols("wins"),
+            cols("p"),
+        ]
+        .concat()
+        .join(",");
+        csv.push('\n');
+
+        for (state, odds) in self.table() {
+            let row = [
+                vec![(state.player + 1).to_string()],
+                state.pos.iter().map(|v| v.to_string()).collect(),
+                state.scores.iter().map(|v| v.to_string()).collect(),
+                odds.wins.iter().map(|v| v.to_string()).collect(),
+                odds.prob.iter().map(|v| v.to_string()).collect(),
+            ]
+            .concat();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
     }
-    *wins.iter().max().unwrap()
 }
 
-const BOARD_SZ: usize = 10;
+fn parse_starts(lines: &[String]) -> Vec<usize> {
+    // Player 1 starting position: 4
+    lines
+        .iter()
+        .map(|l| l.rsplit_once(' ').unwrap().1.parse::<usize>().unwrap())
+        .collect()
+}
 
 struct Pawn {
     space: usize,
@@ -91,61 +233,87 @@ impl Pawn {
         Pawn { space, score: 0 }
     }
 
-    fn move_pawn(&mut self, dist: usize) {
-        self.space = ((self.space + dist - 1) % BOARD_SZ) + 1;
+    fn move_pawn(&mut self, dist: usize, board_sz: usize) {
+        self.space = ((self.space + dist - 1) % board_sz) + 1;
         self.score += self.space;
     }
 }
 
-struct DetDie(usize);
+struct DetDie {
+    /// The face that will come up next
+    next: usize,
+    faces: usize,
+    /// How many times the die has been rolled so far
+    rolled: usize,
+}
 
 impl DetDie {
-    fn new() -> Self {
-        Self(1)
+    fn new(faces: usize) -> Self {
+        DetDie {
+            next: 1,
+            faces,
+            rolled: 0,
+        }
     }
 
     fn next(&mut self, count: usize) -> usize {
-        let r = ((self.0)..(self.0 + count)).sum::<usize>();
-        self.0 += count;
+        let mut r = 0;
+        for _ in 0..count {
+            r += self.next;
+            self.next = (self.next % self.faces) + 1;
+        }
+        self.rolled += count;
         r
     }
 }
 
-fn play_part1(p1_start: usize, p2_start: usize) -> (Pawn, Pawn, DetDie) {
-    let mut die = DetDie::new();
-    let mut p1 = Pawn::new(p1_start);
-    let mut p2 = Pawn::new(p2_start);
-
-    loop {
-        p1.move_pawn(die.next(3));
-        if p1.score >= 1000 {
-            break;
-        }
+fn play_part1(starts: &[usize], rules: &Rules) -> (Vec<Pawn>, DetDie) {
+    let mut die = DetDie::new(rules.faces);
+    let mut pawns = starts.iter().map(|&s| Pawn::new(s)).collect::<Vec<_>>();
 
-        p2.move_pawn(die.next(3));
-        if p2.score >= 1000 {
-            break;
+    'game: loop {
+        for p in pawns.iter_mut() {
+            p.move_pawn(die.next(rules.rolls), rules.board_sz);
+            if p.score >= rules.target {
+                break 'game;
+            }
         }
     }
 
-    (p1, p2, die)
+    (pawns, die)
 }
 
 #[cfg(test)]
 mod day21_tests {
 
+    use aoc2021::str_as_vec;
+
     use super::*;
 
+    fn big(counts: &[u128]) -> Vec<BigUint> {
+        counts.iter().map(|&c| BigUint::from(c)).collect()
+    }
+
     #[test]
     fn test_die() {
-        let mut d = DetDie::new();
+        let mut d = DetDie::new(100);
 
         assert_eq!(d.next(1), 1);
-        assert_eq!(d.0, 2);
+        assert_eq!(d.next, 2);
         assert_eq!(d.next(2), 5);
-        assert_eq!(d.0, 4);
+        assert_eq!(d.next, 4);
         assert_eq!(d.next(3), 15);
-        assert_eq!(d.0, 7);
+        assert_eq!(d.next, 7);
+        assert_eq!(d.rolled, 6);
+    }
+
+    #[test]
+    fn test_die_wraps() {
+        let mut d = DetDie::new(3);
+
+        assert_eq!(d.next(4), 1 + 2 + 3 + 1);
+        assert_eq!(d.next, 2);
+        assert_eq!(d.rolled, 4);
     }
 
     #[test]
@@ -153,35 +321,143 @@ mod day21_tests {
         let mut p1 = Pawn::new(4);
         let mut p2 = Pawn::new(8);
 
-        p1.move_pawn(1 + 2 + 3);
+        p1.move_pawn(1 + 2 + 3, 10);
         assert_eq!(p1.space, 10);
         assert_eq!(p1.score, 10);
 
-        p2.move_pawn(4 + 5 + 6);
+        p2.move_pawn(4 + 5 + 6, 10);
         assert_eq!(p2.space, 3);
         assert_eq!(p2.score, 3);
 
-        p1.move_pawn(7 + 8 + 9);
+        p1.move_pawn(7 + 8 + 9, 10);
         assert_eq!(p1.space, 4);
         assert_eq!(p1.score, 14);
 
-        p2.move_pawn(10 + 11 + 12);
+        p2.move_pawn(10 + 11 + 12, 10);
         assert_eq!(p2.space, 6);
         assert_eq!(p2.score, 9);
 
-        p1.move_pawn(13 + 14 + 15);
+        p1.move_pawn(13 + 14 + 15, 10);
         assert_eq!(p1.space, 6);
         assert_eq!(p1.score, 20);
 
-        p2.move_pawn(16 + 17 + 18);
+        p2.move_pawn(16 + 17 + 18, 10);
         assert_eq!(p2.space, 7);
         assert_eq!(p2.score, 16);
     }
 
+    #[test]
+    fn test_parse() {
+        let lines = str_as_vec("Player 1 starting position: 4\nPlayer 2 starting position: 8");
+        assert_eq!(vec![4, 8], parse_starts(&lines));
+    }
+
     #[test]
     fn test_case() {
-        let (p1, p2, die) = play_part1(4, 8);
-        assert_eq!(die.0 - 1, 993);
-        assert_eq!(p2.score, 745);
+        let (pawns, die) = play_part1(&[4, 8], &PRACTICE);
+        assert_eq!(die.rolled, 993);
+        assert_eq!(pawns[1].score, 745);
+    }
+
+    #[test]
+    fn test_roll_counts() {
+        assert_eq!(
+            vec![(3, 1), (4, 3), (5, 6), (6, 7), (7, 6), (8, 3), (9, 1)],
+            roll_counts(3, 3)
+        );
+        assert_eq!(vec![(1, 1), (2, 1)], roll_counts(2, 1));
+    }
+
+    #[test]
+    fn test_case_quantum() {
+        assert_eq!(
+            big(&[444356092776315, 341960390180808]),
+            play_quantum(&[4, 8], &DIRAC)
+        );
+    }
+
+    #[test]
+    fn test_variants() {
+        // with a coin and one flip per turn, whoever goes first to 2 on a two-space board...
+        let coin = Rules {
+            board_sz: 2,
+            target: 2,
+            faces: 2,
+            rolls: 1,
+        };
+        // ...either lands on 2 and wins, or lands on 1 and waits for the next turn
+        assert_eq!(big(&[3, 1]), play_quantum(&[1, 1], &coin));
+        // three players all get a turn
+        let short = Rules {
+            target: 10,
+            ..DIRAC
+        };
+        let wins = play_quantum(&[1, 1, 1], &short);
+        assert_eq!(3, wins.len());
+        assert!(wins.iter().all(|w| *w > BigUint::default()));
+    }
+
+    #[test]
+    fn test_odds() {
+        let coin = Rules {
+            board_sz: 2,
+            target: 2,
+            faces: 2,
+            rolls: 1,
+        };
+        let mut q = Quantum::new(&coin);
+        let odds = q.odds(&State::new(&[1, 1]));
+        assert_eq!(big(&[3, 1]), odds.wins);
+        assert_eq!(vec![0.75, 0.25], odds.prob);
+
+        // start, after p1 lands on 1, and after p2 does too
+        assert_eq!(
+            "\
+player,pos_1,pos_2,score_1,score_2,wins_1,wins_2,p_1,p_2
+1,1,1,0,0,3,1,0.75,0.25
+1,1,1,1,1,2,0,1,0
+2,1,1,1,0,2,1,0.5,0.5
+",
+            q.to_csv()
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let mut q = Quantum::new(&DIRAC);
+        q.odds(&State::new(&[4, 8]));
+        let table = q.table();
+        assert!(table.len() > 1);
+        for (state, odds) in table {
+            // every game ends with somebody winning
+            let total = odds.prob.iter().sum::<f64>();
+            assert!((total - 1.0).abs() < 1e-9, "{:?} {:?}", state, odds);
+        }
+    }
+
+    #[test]
+    fn test_big_counts() {
+        // far more universes than fit in a u64
+        let long = Rules {
+            target: 40,
+            ..DIRAC
+        };
+        let wins = play_quantum(&[4, 8], &long);
+        assert!(wins.iter().sum::<BigUint>() > BigUint::from(u64::MAX));
+
+        // and by 60, more than fit in a u128
+        let longer = Rules {
+            target: 60,
+            ..DIRAC
+        };
+        let wins = play_quantum(&[4, 8], &longer);
+        assert_eq!(
+            vec![
+                "63458687922806448168000577830986440225677",
+                "57510780644337492876421127199018272043426"
+            ],
+            wins.iter().map(|w| w.to_string()).collect::<Vec<_>>()
+        );
+        assert_eq!(137, wins.iter().sum::<BigUint>().bits());
     }
 }
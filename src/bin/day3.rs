@@ -1,143 +1,247 @@
 use aoc2021::read_lines_from;
 
 pub fn main() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+    // `day3 one|zero|error` picks how tied columns are treated; the puzzle prefers 1
+    let tie = match std::env::args().nth(1).as_deref() {
+        None | Some("one") => Tie::PreferOne,
+        Some("zero") => Tie::PreferZero,
+        Some("error") => Tie::Error,
+        Some(t) => panic!("unknown tie policy `{}`", t),
+    };
+
+    println!("Part 1: {}", part1(tie));
+    println!("Part 2: {}", part2(tie));
 }
 
-fn part1() -> u64 {
+fn part1(tie: Tie) -> u64 {
     let lines = &read_lines_from("input/day3.txt")
         .unwrap() // die if we can't read the file
         .collect::<Result<Vec<String>, std::io::Error>>()
         .unwrap();
-    let bitstrs: Vec<Vec<bool>> = lines.iter().map(|l| str_to_bitvec(l)).collect();
-    let out = transpose(&bitstrs);
-    let mf = most_fewest(&out);
-    let c = coll_most_fewest(&mf);
-    let (gamma, epsilon) = (to_u64(&c.0), to_u64(&c.1));
-    gamma * epsilon
+    let report = parse(lines);
+    let rates = report.gamma_epsilon(tie).unwrap();
+    if !rates.tied.is_empty() {
+        eprintln!("gamma/epsilon tied in columns {:?}", rates.tied);
+    }
+    rates.gamma * rates.epsilon
 }
 
-fn part2() -> u64 {
+fn part2(tie: Tie) -> u64 {
     let lines = &read_lines_from("input/day3.txt")
         .unwrap() // die if we can't read the file
         .collect::<Result<Vec<String>, std::io::Error>>()
         .unwrap();
-    let bitstrs: Vec<Vec<bool>> = lines.iter().map(|l| str_to_bitvec(l)).collect();
-    let ox = filter_ox(&bitstrs);
-    let co2 = filter_co2(&bitstrs);
-    to_u64(&ox) * to_u64(&co2)
+    let report = parse(lines);
+    let (oxygen, co2) = (report.oxygen(tie).unwrap(), report.co2(tie).unwrap());
+    if !oxygen.tied.is_empty() || !co2.tied.is_empty() {
+        eprintln!(
+            "oxygen tied in columns {:?}, CO2 in columns {:?}",
+            oxygen.tied, co2.tied
+        );
+    }
+    oxygen.value * co2.value
 }
 
-/// turn a slice of bits into a number
-fn to_u64(slice: &[bool]) -> u64 {
-    slice
-        .iter()
-        .rev()
-        .enumerate()
-        .fold(0, |acc, (i, &b)| acc + ((b as u64) << i))
+/// What to do when a column has as many 1s as 0s
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Tie {
+    /// Treat 1 as the most common bit, and 0 as the least
+    PreferOne,
+    /// Treat 0 as the most common bit, and 1 as the least
+    PreferZero,
+    /// Give up
+    Error,
 }
 
-fn str_to_bitvec(bitstr: &str) -> Vec<bool> {
-    bitstr
-        .chars()
-        .map(|c| match c {
-            '0' => Ok(false),
-            '1' => Ok(true),
-            _ => Err(()),
-        })
-        .collect::<Result<Vec<bool>, ()>>()
-        .unwrap() // die if we get anything but a 1 or a 0
+/// Some columns were tied, and the tie policy said not to break them
+#[derive(Debug, Eq, PartialEq)]
+struct TieError {
+    /// The tied columns, counting from the left
+    tied: Vec<usize>,
 }
 
-fn transpose(data: &[Vec<bool>]) -> Vec<Vec<bool>> {
-    let mut out: Vec<Vec<bool>> = (0..(data[0].len())).map(|_| Vec::new()).collect();
-    data.iter()
-        .for_each(|l| l.iter().enumerate().for_each(|(i, &d)| out[i].push(d)));
-    out
+/// Reasons a report can't give us a rate or rating
+#[derive(Debug, Eq, PartialEq)]
+enum ReportError {
+    /// There are no lines in the report
+    Empty,
+    /// The lines have no bits in them
+    NoColumns,
+    /// A tie we weren't allowed to break
+    Tie(TieError),
 }
 
-/// Given a Vec, return a tuple with the more- and less- common value in the vec, respectively. Do
-/// this for every Vec in the list
-fn most_fewest(data: &[Vec<bool>]) -> Vec<Option<(bool, bool)>> {
-    data.iter()
-        .map(|v| {
-            v.iter().fold((0, 0), |acc, v| match v {
-                true => (acc.0 + 1, acc.1),
-                false => (acc.0, acc.1 + 1),
-            })
-        })
-        .map(|(true_ct, false_ct)| {
-            if true_ct == false_ct {
-                return None;
-            }
+impl From<TieError> for ReportError {
+    fn from(e: TieError) -> Self {
+        ReportError::Tie(e)
+    }
+}
+
+/// Gamma and epsilon rates, and the columns where a tie had to be broken to get them
+#[derive(Debug, Eq, PartialEq)]
+struct Rates {
+    gamma: u64,
+    epsilon: u64,
+    tied: Vec<usize>,
+}
+
+/// A life support rating, and the columns where a tie had to be broken to get it
+#[derive(Debug, Eq, PartialEq)]
+struct Rating {
+    value: u64,
+    tied: Vec<usize>,
+}
 
-            if true_ct > false_ct {
-                Some((true, false))
-            } else {
-                Some((false, true))
+impl Tie {
+    /// Decide which bit is the most common, given how many of each there are
+    fn most_common(self, col: usize, ones: usize, zeros: usize) -> Result<bool, TieError> {
+        if ones != zeros {
+            return Ok(ones > zeros);
+        }
+        match self {
+            Tie::PreferOne => Ok(true),
+            Tie::PreferZero => Ok(false),
+            Tie::Error => Err(TieError { tied: vec![col] }),
+        }
+    }
+}
+
+/// A diagnostic report, with each line packed into a word
+#[derive(Debug, Eq, PartialEq)]
+struct Report {
+    /// Every line, sorted
+    words: Vec<u64>,
+    /// How many bits are in each line
+    width: usize,
+}
+
+fn parse<S: AsRef<str>>(lines: &[S]) -> Report {
+    let width = lines.first().map_or(0, |l| l.as_ref().len());
+    assert!(width <= 64, "lines must fit in a u64");
+
+    let mut words = lines
+        .iter()
+        .map(|l| {
+            let l = l.as_ref();
+            assert_eq!(width, l.len(), "every line must be the same width");
+            if l.is_empty() {
+                return 0;
             }
+            u64::from_str_radix(l, 2).unwrap() // die if we get anything but a 1 or a 0
         })
-        .collect()
+        .collect::<Vec<u64>>();
+    // Sorting puts lines that share a prefix next to each other, which the rating filters rely on
+    words.sort_unstable();
+
+    Report { words, width }
 }
 
-/// Turn a collection of tuples into a tuple of collections
-fn coll_most_fewest(data: &[Option<(bool, bool)>]) -> (Vec<bool>, Vec<bool>) {
-    data.iter()
-        .map(|v| v.unwrap())
-        .fold((Vec::new(), Vec::new()), |(mut m, mut f), v| {
-            m.push(v.0);
-            f.push(v.1);
-            (m, f)
+impl Report {
+    /// Make sure there's something to work out a rate or rating from
+    fn check(&self) -> Result<(), ReportError> {
+        if self.words.is_empty() {
+            Err(ReportError::Empty)
+        } else if self.width == 0 {
+            Err(ReportError::NoColumns)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The mask for the `col`th column, counting from the left
+    fn bit(&self, col: usize) -> u64 {
+        1 << (self.width - 1 - col)
+    }
+
+    /// Count the 1s in each column, left to right
+    fn ones(&self) -> Vec<usize> {
+        let mut ones = vec![0; self.width];
+        for w in self.words.iter() {
+            for (col, count) in ones.iter_mut().enumerate() {
+                *count += usize::from(w & self.bit(col) != 0);
+            }
+        }
+        ones
+    }
+
+    /// Build gamma from the most common bit in each column; epsilon is everything else
+    fn gamma_epsilon(&self, tie: Tie) -> Result<Rates, ReportError> {
+        self.check()?;
+        let total = self.words.len();
+        let mut gamma = 0;
+        let mut tied = Vec::new();
+        for (col, ones) in self.ones().into_iter().enumerate() {
+            if ones * 2 == total {
+                tied.push(col);
+            }
+            // Keep going after an error, so every tied column gets reported
+            if let Ok(true) = tie.most_common(col, ones, total - ones) {
+                gamma |= self.bit(col);
+            }
+        }
+        if tie == Tie::Error && !tied.is_empty() {
+            return Err(TieError { tied }.into());
+        }
+
+        let mask = u64::MAX >> (64 - self.width);
+        Ok(Rates {
+            gamma,
+            epsilon: !gamma & mask,
+            tied,
         })
-}
+    }
 
-fn filter_pop(data: Vec<Vec<bool>>, idx: usize, pop: bool) -> Vec<Vec<bool>> {
-    let t = transpose(&data);
-    let mf = most_fewest(&t);
-    data
-        .into_iter()
-        .filter(|v| {
-            v[idx]
-                == match mf[idx] {
-                    Some(t) => {
-                        if pop {
-                            t.0
-                        } else {
-                            t.1
-                        }
-                    }
-                    None => pop,
-                }
+    /// Narrow the report down to one line, column by column, keeping the lines with the most (or
+    /// least) common bit in that column
+    fn rating(&self, most: bool, tie: Tie) -> Result<Rating, ReportError> {
+        self.check()?;
+        // The lines still in the running always share a prefix, so they're always a contiguous
+        // run of the sorted words. Within that run, the ones with a 0 in the next column come
+        // before the ones with a 1.
+        let mut run = &self.words[..];
+        let mut tied = Vec::new();
+        for col in 0..self.width {
+            if run.len() <= 1 {
+                break;
+            }
+            let bit = self.bit(col);
+            let split = run.partition_point(|w| w & bit == 0);
+            let (zeros, ones) = run.split_at(split);
+            // if every line agrees there's nothing to choose, even if we're after the least common
+            if zeros.is_empty() || ones.is_empty() {
+                continue;
+            }
+            if zeros.len() == ones.len() {
+                tied.push(col);
+            }
+            let keep_ones = tie.most_common(col, ones.len(), zeros.len())? == most;
+            run = if keep_ones { ones } else { zeros };
+        }
+        Ok(Rating {
+            value: run[0],
+            tied,
         })
-        .collect()
-}
+    }
 
-fn filter_ox(bitstrs: &[Vec<bool>]) -> Vec<bool> {
-    let mut data = bitstrs.to_vec();
-    let mut idx = 0;
-    while data.len() > 1 {
-        data = filter_pop(data, idx, true);
-        idx += 1;
+    fn oxygen(&self, tie: Tie) -> Result<Rating, ReportError> {
+        self.rating(true, tie)
     }
-    data[0].clone()
-}
 
-fn filter_co2(bitstrs: &[Vec<bool>]) -> Vec<bool> {
-    let mut data = bitstrs.to_vec();
-    let mut idx = 0;
-    while data.len() > 1 {
-        data = filter_pop(data, idx, false);
-        idx += 1;
+    fn co2(&self, tie: Tie) -> Result<Rating, ReportError> {
+        self.rating(false, tie)
     }
-    data[0].clone()
 }
 
 #[cfg(test)]
 mod day3_tests {
 
+    use aoc2021::str_as_vec;
+
     use super::*;
 
+    /// The puzzle breaks ties in the life support ratings in favour of 1
+    const TIE: Tie = Tie::PreferOne;
+
     const TEST_DATA: &str = "00100
 11110
 10110
@@ -153,96 +257,159 @@ mod day3_tests {
 
     #[test]
     fn test_case() {
-        let bitstrs: Vec<Vec<bool>> = TEST_DATA.lines().map(str_to_bitvec).collect();
-        let out = transpose(&bitstrs);
-        let mf = most_fewest(&out);
-        let c = coll_most_fewest(&mf);
-        assert_eq!(c.0, vec![true, false, true, true, false]);
-        assert_eq!(c.1, vec![false, true, false, false, true]);
-        let (gamma, epsilon) = (to_u64(&c.0), to_u64(&c.1));
-        assert_eq!(gamma, 22);
-        assert_eq!(epsilon, 9);
-
-        let power_consumption = gamma * epsilon;
+        let report = parse(&str_as_vec(TEST_DATA));
+        assert_eq!(report.ones(), vec![7, 5, 8, 7, 5]);
+        let rates = report.gamma_epsilon(Tie::Error).unwrap();
+        assert_eq!(rates.gamma, 22);
+        assert_eq!(rates.epsilon, 9);
+        assert!(rates.tied.is_empty());
+
+        let power_consumption = rates.gamma * rates.epsilon;
         assert_eq!(power_consumption, 198);
     }
 
     #[test]
-    fn test_most_fewest() {
-        let d = vec![vec![false, false, true], vec![true, true, false]];
-
+    fn test_parse() {
+        let report = parse(&["101", "001", "110"]);
         assert_eq!(
-            most_fewest(&d)
-                .into_iter()
-                .map(Option::unwrap)
-                .collect::<Vec<(bool, bool)>>(),
-            vec![(false, true), (true, false)],
+            report,
+            Report {
+                words: vec![0b001, 0b101, 0b110],
+                width: 3
+            }
         );
+        assert_eq!(report.bit(0), 0b100);
+        assert_eq!(report.bit(2), 0b001);
     }
 
     #[test]
-    fn test_to_u64() {
-        assert_eq!(to_u64(&[true]), 0b1);
-        assert_eq!(to_u64(&[true, false]), 0b10);
-        assert_eq!(to_u64(&[true, true]), 0b11);
-        assert_eq!(to_u64(&[true, false, false]), 0b100);
-        assert_eq!(to_u64(&[true, true, true]), 0b111);
-        assert_eq!(to_u64(&[true, false, true, true, false]), 0b10110);
-    }
+    fn test_rating_ties() {
+        // a 2-1 split keeps the majority 0s for oxygen, and the lone 1 for CO2
+        let report = parse(&["01", "01", "11"]);
+        assert_eq!(
+            report.oxygen(TIE),
+            Ok(Rating {
+                value: 0b01,
+                tied: vec![]
+            })
+        );
+        assert_eq!(
+            report.co2(TIE),
+            Ok(Rating {
+                value: 0b11,
+                tied: vec![]
+            })
+        );
 
-    #[test]
-    fn test_coll_most_fewest() {
-        let d = vec![
-            Some((true, false)),
-            Some((false, true)),
-            Some((true, false)),
-        ];
+        let report = parse(&["10", "01"]);
         assert_eq!(
-            coll_most_fewest(&d),
-            (vec![true, false, true], vec![false, true, false])
+            report.oxygen(Tie::PreferOne),
+            Ok(Rating {
+                value: 0b10,
+                tied: vec![0]
+            })
+        );
+        assert_eq!(report.co2(Tie::PreferOne).unwrap().value, 0b01);
+        assert_eq!(report.oxygen(Tie::PreferZero).unwrap().value, 0b01);
+        assert_eq!(report.co2(Tie::PreferZero).unwrap().value, 0b10);
+        assert_eq!(
+            report.oxygen(Tie::Error),
+            Err(ReportError::Tie(TieError { tied: vec![0] }))
         );
     }
 
     #[test]
-    fn test_transpose() {
-        let res = vec![
-            vec![false, true],
-            vec![false, true],
-            vec![true, true],
-            vec![false, true],
-            vec![false, false],
-        ];
-
+    fn test_rates_ties() {
+        // the first and last columns are tied
+        let report = parse(&["110", "011", "110", "001"]);
         assert_eq!(
-            transpose(&res),
-            vec![
-                vec![false, false, true, false, false],
-                vec![true, true, true, true, false],
-            ]
+            report.gamma_epsilon(Tie::PreferOne),
+            Ok(Rates {
+                gamma: 0b111,
+                epsilon: 0b000,
+                tied: vec![0, 2]
+            })
         );
+        assert_eq!(
+            report.gamma_epsilon(Tie::PreferZero),
+            Ok(Rates {
+                gamma: 0b010,
+                epsilon: 0b101,
+                tied: vec![0, 2]
+            })
+        );
+        assert_eq!(
+            report.gamma_epsilon(Tie::Error),
+            Err(ReportError::Tie(TieError { tied: vec![0, 2] }))
+        );
+    }
+
+    #[test]
+    fn test_rating_unanimous_column() {
+        // every line has a 0 first, so CO2 can't keep the (empty) 1s
+        let report = parse(&["00", "01"]);
+        assert_eq!(report.co2(TIE).unwrap().value, 0b00);
+        assert_eq!(report.oxygen(TIE).unwrap().value, 0b01);
     }
 
     #[test]
-    fn test_filter_pop() {
-        let res = vec![vec![false, true], vec![false, true], vec![true, true]];
+    fn test_unanimous_column_policies() {
+        // a column where every line agrees isn't a tie, whatever the policy; here the first two
+        // columns agree and the last has more 0s, so nothing is ever tied
+        let report = parse(&["010", "011", "010"]);
+        for tie in [Tie::PreferOne, Tie::PreferZero, Tie::Error] {
+            assert_eq!(
+                report.oxygen(tie),
+                Ok(Rating {
+                    value: 0b010,
+                    tied: vec![]
+                })
+            );
+            assert_eq!(
+                report.co2(tie),
+                Ok(Rating {
+                    value: 0b011,
+                    tied: vec![]
+                })
+            );
+        }
+    }
 
-        assert_eq!(
-            filter_pop(res.clone(), 0, true),
-            vec![vec![false, true], vec![false, true]]
-        );
+    #[test]
+    fn test_empty_report() {
+        let report = parse::<&str>(&[]);
+        assert_eq!(report.gamma_epsilon(TIE), Err(ReportError::Empty));
+        assert_eq!(report.oxygen(TIE), Err(ReportError::Empty));
+        assert_eq!(report.co2(TIE), Err(ReportError::Empty));
 
-        assert_eq!(filter_pop(res, 0, false), vec![vec![true, true]],);
+        let report = parse(&["", ""]);
+        assert_eq!(report.gamma_epsilon(TIE), Err(ReportError::NoColumns));
+        assert_eq!(report.oxygen(TIE), Err(ReportError::NoColumns));
+        assert_eq!(report.co2(TIE), Err(ReportError::NoColumns));
     }
 
     #[test]
     fn test_filter_ox() {
-        let bitstrs: Vec<Vec<bool>> = TEST_DATA.lines().map(str_to_bitvec).collect();
-        assert_eq!(filter_ox(&bitstrs), vec![true, false, true, true, true]);
+        let report = parse(&str_as_vec(TEST_DATA));
+        assert_eq!(report.oxygen(TIE).unwrap().value, 0b10111);
     }
 
     #[test]
     fn test_filter_co2() {
-        let bitstrs: Vec<Vec<bool>> = TEST_DATA.lines().map(str_to_bitvec).collect();
-        assert_eq!(filter_co2(&bitstrs), vec![false, true, false, true, false]);
+        let report = parse(&str_as_vec(TEST_DATA));
+        assert_eq!(report.co2(TIE).unwrap().value, 0b01010);
+    }
+
+    #[test]
+    fn test_wide_report() {
+        // an odd number of lines can never tie
+        let lines = (0..999u64)
+            .map(|i| format!("{:064b}", i.wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+            .collect::<Vec<String>>();
+        let report = parse(&lines);
+        let rates = report.gamma_epsilon(Tie::Error).unwrap();
+        assert_eq!(u64::MAX, rates.gamma | rates.epsilon);
+        assert!(report.words.contains(&report.oxygen(TIE).unwrap().value));
+        assert!(report.words.contains(&report.co2(TIE).unwrap().value));
     }
 }
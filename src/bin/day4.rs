@@ -1,767 +1,427 @@
 use aoc2021::read_lines_from;
 
+use std::collections::HashMap;
+
 fn main() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+    // `day4 row,diagonal,corners,...` plays with other win patterns
+    let patterns = match std::env::args().nth(1) {
+        Some(arg) => arg
+            .split(',')
+            .map(|p| Pattern::try_from(p).unwrap())
+            .collect(),
+        None => vec![Pattern::Row, Pattern::Column],
+    };
+
+    println!("Part 1: {}", part1(&patterns));
+    println!("Part 2: {}", part2(&patterns));
 }
 
-fn part1() -> u64 {
-    let lines = &mut read_lines_from("input/day4.txt")
+fn part1(patterns: &[Pattern]) -> u64 {
+    let lines = read_lines_from("input/day4.txt")
         .unwrap() // die if we can't read the file
         .collect::<Result<Vec<String>, std::io::Error>>()
         .unwrap();
 
-    let first_line = &lines.drain(0..1).collect::<Vec<String>>()[0];
-    let moves: Vec<&str> = first_line.split(',').collect();
-
-    let mut boards = load_boards(lines);
-
-    let winner = find_winner(&moves, &mut boards);
-    let tot: u64 = winner
-        .1
-        .tiles
-        .iter()
-        .filter(|t| !t.marked)
-        .map(|t| t.value.parse::<u64>().unwrap())
-        .sum();
-    winner.0.parse::<u64>().unwrap() * tot
+    let mut game = parse_game(&lines);
+    game.patterns = patterns.to_vec();
+    game.wins().next().unwrap().score
 }
 
-fn part2() -> u64 {
-    let lines = &mut read_lines_from("input/day4.txt")
+fn part2(patterns: &[Pattern]) -> u64 {
+    let lines = read_lines_from("input/day4.txt")
         .unwrap() // die if we can't read the file
         .collect::<Result<Vec<String>, std::io::Error>>()
         .unwrap();
 
-    let first_line = &lines.drain(0..1).collect::<Vec<String>>()[0];
-    let moves: Vec<&str> = first_line.split(',').collect();
+    let mut game = parse_game(&lines);
+    game.patterns = patterns.to_vec();
+    game.wins().last().unwrap().score
+}
 
-    let mut boards = load_boards(lines);
+/// Read the drawn numbers from the first line, then boards separated by blank lines
+fn parse_game(lines: &[String]) -> BingoGame {
+    let draws = lines[0]
+        .split(',')
+        .map(|n| n.parse::<u64>().unwrap())
+        .collect();
+
+    BingoGame {
+        draws,
+        boards: load_boards(&lines[1..]),
+        patterns: vec![Pattern::Row, Pattern::Column],
+    }
+}
+
+fn load_boards(lines: &[String]) -> Vec<Board> {
+    lines
+        .split(|l| l.trim().is_empty())
+        .filter(|b| !b.is_empty())
+        .map(make_board)
+        .collect()
+}
 
-    let winner = find_loser(&moves, &mut boards);
-    let tot: u64 = winner
-        .1
-        .tiles
+fn make_board(lines: &[String]) -> Board {
+    let rows = lines
         .iter()
-        .filter(|t| !t.marked)
-        .map(|t| t.value.parse::<u64>().unwrap())
-        .sum();
-    winner.0.parse::<u64>().unwrap() * tot
+        .map(|line| {
+            line.split_whitespace()
+                .map(|v| v.parse::<u64>().unwrap())
+                .collect::<Vec<u64>>()
+        })
+        .collect::<Vec<_>>();
+    Board::new(rows[0].len(), rows.len(), rows.concat())
 }
 
-fn find_loser<'a>(moves: &[&'a str], boards: &'a mut [Board]) -> (&'a str, Board<'a>) {
-    let mut loser = None;
-    let mut remain: Vec<usize> = (0..boards.len()).collect();
-    'outer: for num in moves {
-        let (mut w, t): (Vec<usize>, Vec<usize>) = remain
-            .iter()
-            .partition(|&&i| boards[i].play(num) == Some(Outcome::Bingo));
-        if t.is_empty() {
-            loser = Some((*num, boards[w.pop().unwrap()].clone()));
-            break 'outer;
+/// A game of bingo: the numbers that will be drawn, everyone's boards, and what counts as a win
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct BingoGame {
+    draws: Vec<u64>,
+    boards: Vec<Board>,
+    /// A board wins as soon as it completes any one of these
+    patterns: Vec<Pattern>,
+}
+
+/// A set of tiles that wins once every one of them is marked
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum Pattern {
+    /// Any whole row
+    Row,
+    /// Any whole column
+    Column,
+    /// Either corner-to-corner diagonal, on a square board
+    Diagonal,
+    /// The four corners
+    Corners,
+    /// Every tile on the board
+    FullCard,
+    /// Exactly the tiles that are set, on a board the same shape as the mask
+    Mask(Vec<Vec<bool>>),
+}
+
+/// Errors resulting from attempts to parse a win pattern
+#[derive(Debug, PartialEq, Eq)]
+struct PatternParseError;
+
+impl TryFrom<&str> for Pattern {
+    type Error = PatternParseError;
+
+    /// Parse a pattern's name, or a mask drawn with `#` and `.` with rows separated by `/`
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "row" => Ok(Pattern::Row),
+            "column" => Ok(Pattern::Column),
+            "diagonal" => Ok(Pattern::Diagonal),
+            "corners" => Ok(Pattern::Corners),
+            "full" => Ok(Pattern::FullCard),
+            _ => s
+                .split('/')
+                .map(|row| {
+                    row.chars()
+                        .map(|c| match c {
+                            '#' => Ok(true),
+                            '.' => Ok(false),
+                            _ => Err(PatternParseError),
+                        })
+                        .collect()
+                })
+                .collect::<Result<Vec<Vec<bool>>, PatternParseError>>()
+                .map(Pattern::Mask),
         }
-        remain = t;
     }
-    loser.unwrap()
 }
 
-fn find_winner<'a>(moves: &[&'a str], boards: &'a mut [Board]) -> (&'a str, Board<'a>) {
-    let mut winner = None;
-    'outer: for num in moves {
-        for board in boards.iter_mut() {
-            if board.play(num) == Some(Outcome::Bingo) {
-                winner = Some((*num, board.clone()));
-                break 'outer;
-            }
+/// A board getting bingo
+#[derive(Debug, Eq, PartialEq, Clone)]
+struct Win {
+    /// Which board won
+    board: usize,
+    /// The number that was drawn when it won
+    number: u64,
+    /// The board's score: the winning number times the sum of its unmarked tiles
+    score: u64,
+    /// The pattern the board completed
+    pattern: Pattern,
+}
+
+impl BingoGame {
+    /// Play the game through, yielding each board as it wins
+    fn wins(self) -> Wins {
+        Wins {
+            won: vec![false; self.boards.len()],
+            game: self,
+            next_draw: 0,
+            pending: Vec::new(),
         }
     }
-    winner.unwrap()
 }
 
-fn load_boards(lines: &[String]) -> Vec<Board> {
-    lines
-        .chunks(6) // 5 board lines, one (maybe) gap line
-        .map(make_board)
-        .collect()
+/// The boards of a game, in the order they win. Once a board has won, it stops playing.
+struct Wins {
+    game: BingoGame,
+    won: Vec<bool>,
+    next_draw: usize,
+    /// Boards that won on the same number, waiting their turn (last first)
+    pending: Vec<Win>,
 }
 
-fn make_board(lines: &[String]) -> Board {
-    Board {
-        tiles: lines
-            .iter()
-            .flat_map(|line| line.split_whitespace())
-            .map(|v| Tile {
-                value: v,
-                marked: false,
-            })
-            .collect(),
+impl Iterator for Wins {
+    type Item = Win;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let number = *self.game.draws.get(self.next_draw)?;
+            self.next_draw += 1;
+
+            for (i, board) in self.game.boards.iter_mut().enumerate() {
+                if self.won[i] {
+                    continue;
+                }
+                if let Some(Outcome::Bingo(pattern)) = board.play(number, &self.game.patterns) {
+                    self.won[i] = true;
+                    self.pending.push(Win {
+                        board: i,
+                        number,
+                        score: number * board.unmarked(),
+                        pattern,
+                    });
+                }
+            }
+            self.pending.reverse();
+        }
+        self.pending.pop()
     }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 enum Outcome {
-    Bingo,
+    /// The board completed this pattern
+    Bingo(Pattern),
     Nothing,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct Board<'t> {
-    tiles: Vec<Tile<'t>>,
+struct Board {
+    width: usize,
+    height: usize,
+    /// Row by row
+    tiles: Vec<Tile>,
+    /// Where each value is on the board
+    index: HashMap<u64, usize>,
+    /// How many tiles are marked in each row
+    row_marks: Vec<usize>,
+    /// How many tiles are marked in each column
+    col_marks: Vec<usize>,
+    /// How many tiles are marked altogether
+    marks: usize,
 }
 
-impl<'a> Board<'a> {
-    pub fn play(&mut self, value: &str) -> Option<Outcome> {
-        let mut exists = false;
-        for tile in self.tiles.iter_mut() {
-            if tile.value == value {
-                tile.marked = true;
-                exists = true;
-                break;
-            }
+impl Board {
+    /// Lay out `values`, row by row, on a board `width` tiles across and `height` tiles down
+    pub fn new(width: usize, height: usize, values: Vec<u64>) -> Self {
+        assert_eq!(width * height, values.len(), "board is the wrong size");
+        Board {
+            width,
+            height,
+            index: values.iter().enumerate().map(|(i, &v)| (v, i)).collect(),
+            tiles: values
+                .into_iter()
+                .map(|value| Tile {
+                    value,
+                    marked: false,
+                })
+                .collect(),
+            row_marks: vec![0; height],
+            col_marks: vec![0; width],
+            marks: 0,
         }
-        if !exists {
-            return None;
+    }
+
+    /// Mark `value`, if it's on the board, and see whether that completed any of `patterns`
+    pub fn play(&mut self, value: u64, patterns: &[Pattern]) -> Option<Outcome> {
+        let &i = self.index.get(&value)?;
+        if !self.tiles[i].marked {
+            self.tiles[i].marked = true;
+            self.row_marks[i / self.width] += 1;
+            self.col_marks[i % self.width] += 1;
+            self.marks += 1;
         }
 
-        if self.check_vert(0)
-            || self.check_vert(1)
-            || self.check_vert(2)
-            || self.check_vert(3)
-            || self.check_vert(4)
-            || self.check_horiz(0)
-            || self.check_horiz(5)
-            || self.check_horiz(10)
-            || self.check_horiz(15)
-            || self.check_horiz(20)
-        {
-            Some(Outcome::Bingo)
-        } else {
-            Some(Outcome::Nothing)
+        Some(match patterns.iter().find(|p| self.check_pattern(p, i)) {
+            Some(p) => Outcome::Bingo(p.clone()),
+            None => Outcome::Nothing,
+        })
+    }
+
+    /// Whether marking the tile at `i` completed `pattern`
+    fn check_pattern(&self, pattern: &Pattern, i: usize) -> bool {
+        let (row, col) = (i / self.width, i % self.width);
+        let last = self.width - 1;
+        match pattern {
+            Pattern::Row => self.check_horiz(row),
+            Pattern::Column => self.check_vert(col),
+            Pattern::Diagonal => {
+                self.width == self.height
+                    && ((row == col && (0..self.width).all(|k| self.marked(k, k)))
+                        || (row + col == last && (0..self.width).all(|k| self.marked(k, last - k))))
+            }
+            Pattern::Corners => {
+                let bottom = self.height - 1;
+                [(0, 0), (0, last), (bottom, 0), (bottom, last)]
+                    .iter()
+                    .all(|&(r, c)| self.marked(r, c))
+            }
+            Pattern::FullCard => self.marks == self.tiles.len(),
+            Pattern::Mask(mask) => {
+                mask.len() == self.height
+                    && mask.iter().all(|r| r.len() == self.width)
+                    && mask[row][col]
+                    && mask
+                        .iter()
+                        .flatten()
+                        .zip(self.tiles.iter())
+                        .all(|(&m, t)| !m || t.marked)
+            }
         }
     }
 
-    fn check_vert(&'a self, i: usize) -> bool {
-        [i, i + 5, i + 10, i + 15, i + 20]
-            .iter()
-            .map(|&i| self.tiles[i].marked)
-            .all(|m| m)
+    fn marked(&self, row: usize, col: usize) -> bool {
+        self.tiles[row * self.width + col].marked
+    }
+
+    fn check_vert(&self, col: usize) -> bool {
+        self.col_marks[col] == self.height
     }
 
-    fn check_horiz(&'a self, i: usize) -> bool {
-        (i..i + 5).map(|i| self.tiles[i].marked).all(|m| m)
+    fn check_horiz(&self, row: usize) -> bool {
+        self.row_marks[row] == self.width
+    }
+
+    /// Sum the values of all the unmarked tiles
+    fn unmarked(&self) -> u64 {
+        self.tiles
+            .iter()
+            .filter(|t| !t.marked)
+            .map(|t| t.value)
+            .sum()
     }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct Tile<'v> {
-    value: &'v str,
+struct Tile {
+    value: u64,
     marked: bool,
 }
 
 #[cfg(test)]
 mod day4_tests {
 
+    use aoc2021::str_as_vec;
+
     use super::*;
 
-    #[test]
-    fn test_read_board() {
-        let test_data = "22 13 17 11  0
+    const TEST_BOARD: &str = "22 13 17 11  0
  8  2 23  4 24
 21  9 14 16  7
  6 10  3 18  5
  1 12 20 15 19
-
 ";
-        let res = Board {
-            tiles: vec![
-                Tile {
-                    value: "22",
-                    marked: false,
-                },
-                Tile {
-                    value: "13",
-                    marked: false,
-                },
-                Tile {
-                    value: "17",
-                    marked: false,
-                },
-                Tile {
-                    value: "11",
-                    marked: false,
-                },
-                Tile {
-                    value: "0",
-                    marked: false,
-                },
-                Tile {
-                    value: "8",
-                    marked: false,
-                },
-                Tile {
-                    value: "2",
-                    marked: false,
-                },
-                Tile {
-                    value: "23",
-                    marked: false,
-                },
-                Tile {
-                    value: "4",
-                    marked: false,
-                },
-                Tile {
-                    value: "24",
-                    marked: false,
-                },
-                Tile {
-                    value: "21",
-                    marked: false,
-                },
-                Tile {
-                    value: "9",
-                    marked: false,
-                },
-                Tile {
-                    value: "14",
-                    marked: false,
-                },
-                Tile {
-                    value: "16",
-                    marked: false,
-                },
-                Tile {
-                    value: "7",
-                    marked: false,
-                },
-                Tile {
-                    value: "6",
-                    marked: false,
-                },
-                Tile {
-                    value: "10",
-                    marked: false,
-                },
-                Tile {
-                    value: "3",
-                    marked: false,
-                },
-                Tile {
-                    value: "18",
-                    marked: false,
-                },
-                Tile {
-                    value: "5",
-                    marked: false,
-                },
-                Tile {
-                    value: "1",
-                    marked: false,
-                },
-                Tile {
-                    value: "12",
-                    marked: false,
-                },
-                Tile {
-                    value: "20",
-                    marked: false,
-                },
-                Tile {
-                    value: "15",
-                    marked: false,
-                },
-                Tile {
-                    value: "19",
-                    marked: false,
-                },
-            ],
-        };
 
-        let d = test_data
-            .lines()
-            .map(str::to_string)
-            .collect::<Vec<String>>();
-        let b = make_board(&d);
+    const LINES: &[Pattern] = &[Pattern::Row, Pattern::Column];
+
+    const TEST_VALUES: [u64; 25] = [
+        22, 13, 17, 11, 0, 8, 2, 23, 4, 24, 21, 9, 14, 16, 7, 6, 10, 3, 18, 5, 1, 12, 20, 15, 19,
+    ];
 
-        assert_eq!(res, b);
+    #[test]
+    fn test_read_board() {
+        let b = make_board(&str_as_vec(TEST_BOARD));
+
+        assert_eq!(b, Board::new(5, 5, TEST_VALUES.to_vec()));
         assert_eq!(b.tiles.len(), 25);
+        assert_eq!(b.index[&24], 9);
     }
 
     #[test]
     fn test_load_boards() {
-        let test_data = "22 13 17 11  0
- 8  2 23  4 24
-21  9 14 16  7
- 6 10  3 18  5
- 1 12 20 15 19
-
-22 13 17 11  0
- 8  2 23  4 24
-21  9 14 16  7
- 6 10  3 18  5
- 1 12 20 15 19
-
-";
-        let res = Board {
-            tiles: vec![
-                Tile {
-                    value: "22",
-                    marked: false,
-                },
-                Tile {
-                    value: "13",
-                    marked: false,
-                },
-                Tile {
-                    value: "17",
-                    marked: false,
-                },
-                Tile {
-                    value: "11",
-                    marked: false,
-                },
-                Tile {
-                    value: "0",
-                    marked: false,
-                },
-                Tile {
-                    value: "8",
-                    marked: false,
-                },
-                Tile {
-                    value: "2",
-                    marked: false,
-                },
-                Tile {
-                    value: "23",
-                    marked: false,
-                },
-                Tile {
-                    value: "4",
-                    marked: false,
-                },
-                Tile {
-                    value: "24",
-                    marked: false,
-                },
-                Tile {
-                    value: "21",
-                    marked: false,
-                },
-                Tile {
-                    value: "9",
-                    marked: false,
-                },
-                Tile {
-                    value: "14",
-                    marked: false,
-                },
-                Tile {
-                    value: "16",
-                    marked: false,
-                },
-                Tile {
-                    value: "7",
-                    marked: false,
-                },
-                Tile {
-                    value: "6",
-                    marked: false,
-                },
-                Tile {
-                    value: "10",
-                    marked: false,
-                },
-                Tile {
-                    value: "3",
-                    marked: false,
-                },
-                Tile {
-                    value: "18",
-                    marked: false,
-                },
-                Tile {
-                    value: "5",
-                    marked: false,
-                },
-                Tile {
-                    value: "1",
-                    marked: false,
-                },
-                Tile {
-                    value: "12",
-                    marked: false,
-                },
-                Tile {
-                    value: "20",
-                    marked: false,
-                },
-                Tile {
-                    value: "15",
-                    marked: false,
-                },
-                Tile {
-                    value: "19",
-                    marked: false,
-                },
-            ],
-        };
-
-        let d = test_data
-            .lines()
-            .map(str::to_string)
-            .collect::<Vec<String>>();
+        let test_data = format!("{}\n{}\n", TEST_BOARD, TEST_BOARD);
+        let res = Board::new(5, 5, TEST_VALUES.to_vec());
 
-        assert_eq!(load_boards(&d), vec![res.clone(), res]);
+        assert_eq!(load_boards(&str_as_vec(&test_data)), vec![res.clone(), res]);
     }
 
     #[test]
     fn test_play() {
-        let mut b = Board {
-            tiles: vec![
-                Tile {
-                    value: "22",
-                    marked: false,
-                },
-                Tile {
-                    value: "13",
-                    marked: false,
-                },
-                Tile {
-                    value: "17",
-                    marked: false,
-                },
-                Tile {
-                    value: "11",
-                    marked: false,
-                },
-                Tile {
-                    value: "0",
-                    marked: false,
-                },
-                Tile {
-                    value: "8",
-                    marked: false,
-                },
-                Tile {
-                    value: "2",
-                    marked: false,
-                },
-                Tile {
-                    value: "23",
-                    marked: false,
-                },
-                Tile {
-                    value: "4",
-                    marked: false,
-                },
-                Tile {
-                    value: "24",
-                    marked: false,
-                },
-                Tile {
-                    value: "21",
-                    marked: false,
-                },
-                Tile {
-                    value: "9",
-                    marked: false,
-                },
-                Tile {
-                    value: "14",
-                    marked: false,
-                },
-                Tile {
-                    value: "16",
-                    marked: false,
-                },
-                Tile {
-                    value: "7",
-                    marked: false,
-                },
-                Tile {
-                    value: "6",
-                    marked: false,
-                },
-                Tile {
-                    value: "10",
-                    marked: false,
-                },
-                Tile {
-                    value: "3",
-                    marked: false,
-                },
-                Tile {
-                    value: "18",
-                    marked: false,
-                },
-                Tile {
-                    value: "5",
-                    marked: false,
-                },
-                Tile {
-                    value: "1",
-                    marked: false,
-                },
-                Tile {
-                    value: "12",
-                    marked: false,
-                },
-                Tile {
-                    value: "20",
-                    marked: false,
-                },
-                Tile {
-                    value: "15",
-                    marked: false,
-                },
-                Tile {
-                    value: "19",
-                    marked: false,
-                },
-            ],
-        };
-
-        assert_eq!(b.play("170"), None);
-        assert_eq!(b.play("22"), Some(Outcome::Nothing));
-        assert_eq!(b.play("13"), Some(Outcome::Nothing));
-        assert_eq!(b.play("17"), Some(Outcome::Nothing));
-        assert_eq!(b.play("11"), Some(Outcome::Nothing));
-        let o = b.play("0");
+        let mut b = Board::new(5, 5, TEST_VALUES.to_vec());
+
+        assert_eq!(b.play(170, LINES), None);
+        assert_eq!(b.play(22, LINES), Some(Outcome::Nothing));
+        assert_eq!(b.play(13, LINES), Some(Outcome::Nothing));
+        assert_eq!(b.play(17, LINES), Some(Outcome::Nothing));
+        assert_eq!(b.play(11, LINES), Some(Outcome::Nothing));
+        let o = b.play(0, LINES);
         assert!(b.check_horiz(0));
-        assert_eq!(o, Some(Outcome::Bingo));
+        assert_eq!(o, Some(Outcome::Bingo(Pattern::Row)));
+        assert_eq!(b.unmarked(), 300 - 63);
     }
 
     #[test]
-    fn test_horiz() {
-        let mut b = Board {
-            tiles: vec![
-                Tile {
-                    value: "22",
-                    marked: true,
-                },
-                Tile {
-                    value: "13",
-                    marked: true,
-                },
-                Tile {
-                    value: "17",
-                    marked: true,
-                },
-                Tile {
-                    value: "11",
-                    marked: true,
-                },
-                Tile {
-                    value: "0",
-                    marked: false,
-                },
-                Tile {
-                    value: "8",
-                    marked: false,
-                },
-                Tile {
-                    value: "2",
-                    marked: false,
-                },
-                Tile {
-                    value: "23",
-                    marked: false,
-                },
-                Tile {
-                    value: "4",
-                    marked: false,
-                },
-                Tile {
-                    value: "24",
-                    marked: false,
-                },
-                Tile {
-                    value: "21",
-                    marked: false,
-                },
-                Tile {
-                    value: "9",
-                    marked: false,
-                },
-                Tile {
-                    value: "14",
-                    marked: false,
-                },
-                Tile {
-                    value: "16",
-                    marked: false,
-                },
-                Tile {
-                    value: "7",
-                    marked: false,
-                },
-                Tile {
-                    value: "6",
-                    marked: false,
-                },
-                Tile {
-                    value: "10",
-                    marked: false,
-                },
-                Tile {
-                    value: "3",
-                    marked: false,
-                },
-                Tile {
-                    value: "18",
-                    marked: false,
-                },
-                Tile {
-                    value: "5",
-                    marked: false,
-                },
-                Tile {
-                    value: "1",
-                    marked: false,
-                },
-                Tile {
-                    value: "12",
-                    marked: false,
-                },
-                Tile {
-                    value: "20",
-                    marked: false,
-                },
-                Tile {
-                    value: "15",
-                    marked: false,
-                },
-                Tile {
-                    value: "19",
-                    marked: false,
-                },
-            ],
-        };
+    fn test_play_twice() {
+        let mut b = Board::new(2, 2, vec![1, 2, 3, 4]);
 
-        assert!(!b.check_horiz(0));
-        assert_eq!(b.play("0"), Some(Outcome::Bingo));
-        assert!(b.check_horiz(0));
+        assert_eq!(b.play(1, LINES), Some(Outcome::Nothing));
+        assert_eq!(b.play(1, LINES), Some(Outcome::Nothing));
+        assert_eq!(b.row_marks, vec![1, 0]);
     }
 
     #[test]
     fn test_vert() {
-        let mut b = Board {
-            tiles: vec![
-                Tile {
-                    value: "22",
-                    marked: false,
-                },
-                Tile {
-                    value: "13",
-                    marked: false,
-                },
-                Tile {
-                    value: "17",
-                    marked: false,
-                },
-                Tile {
-                    value: "11",
-                    marked: false,
-                },
-                Tile {
-                    value: "0",
-                    marked: true,
-                },
-                Tile {
-                    value: "8",
-                    marked: false,
-                },
-                Tile {
-                    value: "2",
-                    marked: false,
-                },
-                Tile {
-                    value: "23",
-                    marked: false,
-                },
-                Tile {
-                    value: "4",
-                    marked: false,
-                },
-                Tile {
-                    value: "24",
-                    marked: true,
-                },
-                Tile {
-                    value: "21",
-                    marked: false,
-                },
-                Tile {
-                    value: "9",
-                    marked: false,
-                },
-                Tile {
-                    value: "14",
-                    marked: false,
-                },
-                Tile {
-                    value: "16",
-                    marked: false,
-                },
-                Tile {
-                    value: "7",
-                    marked: true,
-                },
-                Tile {
-                    value: "6",
-                    marked: false,
-                },
-                Tile {
-                    value: "10",
-                    marked: false,
-                },
-                Tile {
-                    value: "3",
-                    marked: false,
-                },
-                Tile {
-                    value: "18",
-                    marked: false,
-                },
-                Tile {
-                    value: "5",
-                    marked: true,
-                },
-                Tile {
-                    value: "1",
-                    marked: false,
-                },
-                Tile {
-                    value: "12",
-                    marked: false,
-                },
-                Tile {
-                    value: "20",
-                    marked: false,
-                },
-                Tile {
-                    value: "15",
-                    marked: false,
-                },
-                Tile {
-                    value: "19",
-                    marked: false,
-                },
-            ],
-        };
+        let mut b = Board::new(5, 5, TEST_VALUES.to_vec());
 
-        assert!(!b.check_vert(0));
-        assert!(!b.check_vert(1));
-        assert!(!b.check_vert(2));
-        assert!(!b.check_vert(3));
-        assert!(!b.check_vert(4));
-        b.play("15");
+        for col in 0..5 {
+            assert!(!b.check_vert(col));
+        }
+        b.play(0, LINES);
+        b.play(24, LINES);
+        b.play(7, LINES);
+        b.play(5, LINES);
         assert!(!b.check_vert(4));
-        b.play("19");
+        assert_eq!(b.play(19, LINES), Some(Outcome::Bingo(Pattern::Column)));
         assert!(b.check_vert(4));
     }
 
+    #[test]
+    fn test_odd_shapes() {
+        let test_data = "1,2,3,4,5,6
+
+1 2 3
+4 5 6
+
+1 4
+6 2
+3 5
+";
+        let wins = parse_game(&str_as_vec(test_data))
+            .wins()
+            .collect::<Vec<Win>>();
+        assert_eq!(
+            wins,
+            vec![
+                Win {
+                    board: 0,
+                    number: 3,
+                    score: 3 * (4 + 5 + 6),
+                    pattern: Pattern::Row,
+                },
+                Win {
+                    board: 1,
+                    number: 4,
+                    score: 4 * (6 + 5),
+                    pattern: Pattern::Row,
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_case() {
         let test_data = "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
@@ -784,33 +444,104 @@ mod day4_tests {
 22 11 13  6  5
  2  0 12  3  7";
 
-        let mut lines = test_data
-            .lines()
-            .map(str::to_string)
-            .collect::<Vec<String>>();
+        let game = parse_game(&str_as_vec(test_data));
+        assert_eq!(game.draws.len(), 27);
+        assert_eq!(game.boards.len(), 3);
 
-        let first_line = &lines.drain(0..1).collect::<Vec<String>>()[0];
+        let wins = game.wins().collect::<Vec<Win>>();
         assert_eq!(
-            first_line,
-            "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1"
+            wins.iter().map(|w| w.board).collect::<Vec<usize>>(),
+            vec![2, 0, 1]
         );
-        let moves: Vec<&str> = first_line.split(',').collect();
+        assert_eq!(wins[0].number, 24);
+        assert_eq!(wins[0].score, 4512);
+        assert_eq!(wins[2].number, 13);
+        assert_eq!(wins[2].score, 1924);
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        assert_eq!(Ok(Pattern::Diagonal), Pattern::try_from("diagonal"));
+        assert_eq!(Ok(Pattern::FullCard), Pattern::try_from("full"));
         assert_eq!(
-            moves,
-            vec![
-                "7", "4", "9", "5", "11", "17", "23", "2", "0", "14", "21", "24", "10", "16", "13",
-                "6", "15", "25", "12", "22", "18", "20", "8", "19", "3", "26", "1"
-            ]
+            Ok(Pattern::Mask(vec![vec![true, false], vec![false, true]])),
+            Pattern::try_from("#./.#")
         );
+        assert_eq!(Err(PatternParseError), Pattern::try_from("#x/.#"));
+    }
+
+    #[test]
+    fn test_diagonal() {
+        let diag = &[Pattern::Diagonal];
+        let mut b = Board::new(5, 5, TEST_VALUES.to_vec());
+
+        // the row doesn't count
+        for v in [22, 13, 17, 11] {
+            assert_eq!(b.play(v, diag), Some(Outcome::Nothing));
+        }
+        for v in [0, 4, 14, 10] {
+            assert_eq!(b.play(v, diag), Some(Outcome::Nothing));
+        }
+        assert_eq!(b.play(1, diag), Some(Outcome::Bingo(Pattern::Diagonal)));
+
+        // diagonals need a square board
+        let mut b = Board::new(2, 1, vec![1, 2]);
+        b.play(1, diag);
+        assert_eq!(b.play(2, diag), Some(Outcome::Nothing));
+    }
+
+    #[test]
+    fn test_corners_and_full_card() {
+        let patterns = &[Pattern::Corners, Pattern::FullCard];
+        let mut b = Board::new(2, 2, vec![1, 2, 3, 4]);
 
-        let mut boards = load_boards(&lines);
-        let winner = find_winner(&moves, &mut boards);
+        for v in [1, 2, 3] {
+            assert_eq!(b.play(v, &patterns[1..]), Some(Outcome::Nothing));
+        }
+        // the first pattern that matches is the one reported
+        assert_eq!(b.play(4, patterns), Some(Outcome::Bingo(Pattern::Corners)));
+        assert_eq!(
+            b.play(4, &patterns[1..]),
+            Some(Outcome::Bingo(Pattern::FullCard))
+        );
+    }
 
-        assert_eq!(winner.0, "24");
+    #[test]
+    fn test_mask() {
+        let x = Pattern::try_from("#...#/.#.#./..#../.#.#./#...#").unwrap();
+        let mut b = Board::new(5, 5, TEST_VALUES.to_vec());
+        let xs = std::slice::from_ref(&x);
+        for v in [22, 0, 2, 4, 14, 10, 18, 1] {
+            assert_eq!(b.play(v, xs), Some(Outcome::Nothing));
+        }
+        // not part of the mask
+        assert_eq!(b.play(13, xs), Some(Outcome::Nothing));
+        assert_eq!(b.play(19, xs), Some(Outcome::Bingo(x.clone())));
 
-        let mut boards = load_boards(&lines);
-        let loser = find_loser(&moves, &mut boards);
+        // masks only fit boards of the same shape
+        let mut b = Board::new(1, 1, vec![1]);
+        assert_eq!(b.play(1, &[x]), Some(Outcome::Nothing));
+    }
 
-        assert_eq!(loser.0, "13");
+    #[test]
+    fn test_game_patterns() {
+        let test_data = "1,5,9,2,8
+
+1 2 3
+4 5 6
+7 8 9
+
+2 1 3
+5 4 6
+8 9 7";
+        let mut game = parse_game(&str_as_vec(test_data));
+        game.patterns = vec![Pattern::Diagonal, Pattern::Column];
+        let wins = game.wins().collect::<Vec<Win>>();
+        assert_eq!(
+            wins.iter()
+                .map(|w| (w.board, w.number, w.pattern.clone()))
+                .collect::<Vec<_>>(),
+            vec![(0, 9, Pattern::Diagonal), (1, 8, Pattern::Column)]
+        );
     }
 }
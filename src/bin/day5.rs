@@ -1,128 +1,756 @@
 use aoc2021::lines_as_vec;
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 
 fn main() {
-    println!("Solution: {}", solve());
+    // `day5 sparse|dense|sweep` picks how overlaps are counted, `day5 diagram` prints the vent
+    // diagram and `day5 pgm <file>` writes it as a heatmap
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let engine = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] | ["sparse"] => Engine::Sparse,
+        ["dense"] => Engine::Dense,
+        ["sweep"] => Engine::Sweep,
+        ["diagram"] => {
+            let segs = parse_lines(&lines_as_vec("input/day5.txt"));
+            print!("{}", MapPrinter(&rasterise(&segs, |_| true).unwrap()));
+            return;
+        }
+        ["pgm", file] => {
+            let segs = parse_lines(&lines_as_vec("input/day5.txt"));
+            std::fs::write(file, to_pgm(&rasterise(&segs, |_| true).unwrap())).unwrap();
+            return;
+        }
+        _ => panic!("unknown arguments {:?}", args),
+    };
+
+    println!("Part 1: {}", part1(engine));
+    println!("Part 2: {}", part2(engine));
 }
 
-fn solve() -> usize {
-    let lines = lines_as_vec("input/day5.txt");
-    let mut map = HashMap::new();
+fn part1(engine: Engine) -> usize {
+    let segs = parse_lines(&lines_as_vec("input/day5.txt"));
+    count_overlaps_with(&segs, Orientation::is_orthogonal, engine).unwrap()
+}
+
+fn part2(engine: Engine) -> usize {
+    let segs = parse_lines(&lines_as_vec("input/day5.txt"));
+    count_overlaps_with(&segs, |_| true, engine).unwrap()
+}
 
-    lines
+/// Ways of counting the points where vents overlap
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Engine {
+    /// Draw every point into a `HashMap`
+    Sparse,
+    /// Draw every point into a grid big enough to hold all the segments
+    Dense,
+    /// Work out overlaps from the segments' ends, without drawing anything
+    Sweep,
+}
+
+/// Count the points covered by more than one of the segments whose orientation passes `filter`
+fn count_overlaps_with<F>(
+    segs: &[Segment],
+    filter: F,
+    engine: Engine,
+) -> Result<usize, SegmentError>
+where
+    F: Fn(Orientation) -> bool,
+{
+    let segs = segs
         .iter()
-        .map(|l| parse_line(l))
-        .for_each(|t| record_seg(t.0 .0, t.0 .1, t.1 .0, t.1 .1, &mut map));
+        .filter(|s| filter(s.orientation()))
+        .copied()
+        .collect::<Vec<Segment>>();
+    match engine {
+        Engine::Sparse => Ok(count_overlaps(&rasterise(&segs, |_| true)?)),
+        Engine::Dense => count_overlaps_dense(&segs),
+        Engine::Sweep => count_overlaps_sweep(&segs),
+    }
+}
+
+type Map = HashMap<(u64, u64), u64>;
+
+/// A line of vents, from one end to the other
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Segment {
+    from: (u64, u64),
+    to: (u64, u64),
+}
+
+/// Which way a segment runs
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Orientation {
+    Horizontal,
+    Vertical,
+    /// At 45°, either way
+    Diagonal,
+    /// Any other angle
+    Other,
+}
+
+impl Orientation {
+    fn is_orthogonal(self) -> bool {
+        matches!(self, Orientation::Horizontal | Orientation::Vertical)
+    }
+}
+
+/// A segment we can't draw on the grid, because it isn't horizontal, vertical or at 45°, or
+/// reaches too far out to fit
+#[derive(Debug, Eq, PartialEq)]
+struct SegmentError(Segment);
+
+impl Segment {
+    fn orientation(&self) -> Orientation {
+        let (x1, y1) = self.from;
+        let (x2, y2) = self.to;
+        if x1 == x2 {
+            Orientation::Vertical
+        } else if y1 == y2 {
+            Orientation::Horizontal
+        } else if x1.abs_diff(x2) == y1.abs_diff(y2) {
+            Orientation::Diagonal
+        } else {
+            Orientation::Other
+        }
+    }
+
+    /// Every grid point the segment covers, from one end to the other
+    fn points(&self) -> Result<impl Iterator<Item = (u64, u64)>, SegmentError> {
+        if self.orientation() == Orientation::Other {
+            return Err(SegmentError(*self));
+        }
+        let (x1, y1) = self.from;
+        let (x2, y2) = self.to;
+        let len = x1.abs_diff(x2).max(y1.abs_diff(y2));
+        // each coordinate moves one step at a time toward its end, or stays put
+        let step = |a: u64, b: u64, i: u64| match a.cmp(&b) {
+            Ordering::Less => a + i,
+            Ordering::Equal => a,
+            Ordering::Greater => a - i,
+        };
+        Ok((0..=len).map(move |i| (step(x1, x2, i), step(y1, y2, i))))
+    }
+}
 
-    map.values().filter(|&&v| v > 1).cloned().count()
+fn parse_lines(lines: &[String]) -> Vec<Segment> {
+    lines.iter().map(|l| parse_line(l)).collect()
 }
 
-fn parse_line(line: &str) -> ((u64, u64), (u64, u64)) {
+fn parse_line(line: &str) -> Segment {
     let parts: Vec<&str> = line.split(" -> ").collect();
     let p1: Vec<&str> = parts[0].split(',').collect();
     let p2: Vec<&str> = parts[1].split(',').collect();
 
-    (
-        (p1[0].parse::<u64>().unwrap(), p1[1].parse::<u64>().unwrap()),
-        (p2[0].parse::<u64>().unwrap(), p2[1].parse::<u64>().unwrap()),
-    )
+    Segment {
+        from: (p1[0].parse::<u64>().unwrap(), p1[1].parse::<u64>().unwrap()),
+        to: (p2[0].parse::<u64>().unwrap(), p2[1].parse::<u64>().unwrap()),
+    }
 }
 
-fn record_seg(x1: u64, y1: u64, x2: u64, y2: u64, map: &mut HashMap<(u64, u64), u64>) {
-    if x1 == x2 {
-        if y1 < y2 { y1..=y2 } else { y2..=y1 }.for_each(|y| {
-            map.entry((x1, y)).and_modify(|v| *v += 1).or_insert(1);
-        })
-    } else if y1 == y2 {
-        if x1 < x2 { x1..=x2 } else { x2..=x1 }.for_each(|x| {
-            map.entry((x, y1)).and_modify(|v| *v += 1).or_insert(1);
-        })
-    } else {
-        let x: Vec<u64> = if x1 < x2 {
-            (x1..=x2).collect()
-        } else {
-            (x2..=x1).rev().collect()
+/// Draw every segment whose orientation passes `filter`
+fn rasterise<F>(segs: &[Segment], filter: F) -> Result<Map, SegmentError>
+where
+    F: Fn(Orientation) -> bool,
+{
+    let mut map = HashMap::new();
+    for seg in segs.iter().filter(|s| filter(s.orientation())) {
+        record_seg(seg, &mut map)?;
+    }
+    Ok(map)
+}
+
+fn record_seg(seg: &Segment, map: &mut Map) -> Result<(), SegmentError> {
+    seg.points()?.for_each(|p| {
+        map.entry(p).and_modify(|v| *v += 1).or_insert(1);
+    });
+    Ok(())
+}
+
+fn count_overlaps(map: &Map) -> usize {
+    map.values().filter(|&&v| v > 1).count()
+}
+
+fn find_extents(map: &Map) -> (u64, u64) {
+    map.keys()
+        .fold((0, 0), |acc, &(x, y)| (x.max(acc.0), y.max(acc.1)))
+}
+
+/// Draws a map the way the puzzle does: `.` where there are no vents, otherwise how many there are
+///
+/// Counts too big for a single digit are drawn as `#`.
+struct MapPrinter<'a>(&'a Map);
+
+impl std::fmt::Display for MapPrinter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        let (x_max, y_max) = find_extents(self.0);
+        for y in 0..=y_max {
+            for x in 0..=x_max {
+                let c = match self.0.get(&(x, y)).copied().unwrap_or(0) {
+                    0 => '.',
+                    n => u32::try_from(n)
+                        .ok()
+                        .and_then(|n| char::from_digit(n, 10))
+                        .unwrap_or('#'),
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Render a map as a binary greyscale PGM, brightest where the most vents overlap
+fn to_pgm(map: &Map) -> Vec<u8> {
+    let (width, height) = match map.is_empty() {
+        true => (0, 0),
+        false => {
+            let (x_max, y_max) = find_extents(map);
+            (x_max + 1, y_max + 1)
+        }
+    };
+    // PGM samples here are a single byte, so anything busier than 255 is just as bright
+    let max = map.values().copied().max().unwrap_or(0).clamp(1, 255);
+    let mut pgm = format!("P5\n{} {}\n{}\n", width, height, max).into_bytes();
+    for y in 0..height {
+        for x in 0..width {
+            let n = map.get(&(x, y)).copied().unwrap_or(0).min(max);
+            pgm.push(n as u8);
+        }
+    }
+    pgm
+}
+
+/// The most cells the dense engine will allocate, a gigabyte of counters
+const MAX_DENSE_CELLS: usize = 1 << 30;
+
+/// The width and height of a grid that holds every segment
+///
+/// Fails on the first segment that pushes the grid past `MAX_DENSE_CELLS`.
+fn dense_size(segs: &[Segment]) -> Result<(usize, usize), SegmentError> {
+    let (mut width, mut height) = (0, 0);
+    for seg in segs {
+        let err = || SegmentError(*seg);
+        let fit = |a: u64, b: u64| usize::try_from(a.max(b)).ok()?.checked_add(1);
+        width = fit(seg.from.0, seg.to.0).ok_or_else(err)?.max(width);
+        height = fit(seg.from.1, seg.to.1).ok_or_else(err)?.max(height);
+        match width.checked_mul(height) {
+            Some(cells) if cells <= MAX_DENSE_CELLS => (),
+            _ => return Err(err()),
+        }
+    }
+    Ok((width, height))
+}
+
+fn count_overlaps_dense(segs: &[Segment]) -> Result<usize, SegmentError> {
+    let (width, height) = dense_size(segs)?;
+    let mut grid = vec![0u8; width * height];
+    for seg in segs {
+        for (x, y) in seg.points()? {
+            // we only care whether a point is covered more than once
+            let cell = &mut grid[y as usize * width + x as usize];
+            *cell = cell.saturating_add(1);
+        }
+    }
+    Ok(grid.iter().filter(|&&v| v > 1).count())
+}
+
+/// The line a segment lies on, which every other segment on the same line overlaps along
+///
+/// Points on the line are measured by `x`, except on vertical lines, where they're measured by
+/// `y`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Line {
+    /// `y` is the same everywhere
+    Horizontal(i64),
+    /// `x` is the same everywhere
+    Vertical(i64),
+    /// `x - y` is the same everywhere
+    Rising(i64),
+    /// `x + y` is the same everywhere
+    Falling(i64),
+}
+
+impl Line {
+    /// The point `t` along the line
+    fn point(self, t: i64) -> (i64, i64) {
+        match self {
+            Line::Horizontal(y) => (t, y),
+            Line::Vertical(x) => (x, t),
+            Line::Rising(k) => (t, t - k),
+            Line::Falling(k) => (t, k - t),
+        }
+    }
+
+    /// How far along the line `(x, y)` is, if it's on it at all
+    fn locate(self, (x, y): (i64, i64)) -> Option<i64> {
+        let (t, on) = match self {
+            Line::Horizontal(k) => (x, y == k),
+            Line::Vertical(k) => (y, x == k),
+            Line::Rising(k) => (x, x - y == k),
+            Line::Falling(k) => (x, x + y == k),
         };
-        let y: Vec<u64> = if y1 < y2 {
-            (y1..=y2).collect()
-        } else {
-            (y2..=y1).rev().collect()
+        on.then_some(t)
+    }
+
+    /// Every line that passes through `(x, y)`
+    fn through((x, y): (i64, i64)) -> [Line; 4] {
+        [
+            Line::Horizontal(y),
+            Line::Vertical(x),
+            Line::Rising(x - y),
+            Line::Falling(x + y),
+        ]
+    }
+
+    /// Where two lines cross, if they do so on a grid point we can name
+    fn cross(self, other: Line) -> Option<(i64, i64)> {
+        use Line::*;
+        // work in i128, since lines can cross well outside the segments on them
+        let (x, y): (i128, i128) = match (self, other) {
+            (Horizontal(y), Vertical(x)) | (Vertical(x), Horizontal(y)) => (x.into(), y.into()),
+            (Horizontal(y), Rising(k)) | (Rising(k), Horizontal(y)) => {
+                (i128::from(y) + i128::from(k), y.into())
+            }
+            (Horizontal(y), Falling(k)) | (Falling(k), Horizontal(y)) => {
+                (i128::from(k) - i128::from(y), y.into())
+            }
+            (Vertical(x), Rising(k)) | (Rising(k), Vertical(x)) => {
+                (x.into(), i128::from(x) - i128::from(k))
+            }
+            (Vertical(x), Falling(k)) | (Falling(k), Vertical(x)) => {
+                (x.into(), i128::from(k) - i128::from(x))
+            }
+            (Rising(r), Falling(f)) | (Falling(f), Rising(r)) => {
+                let (r, f) = (i128::from(r), i128::from(f));
+                if (r + f) % 2 != 0 {
+                    return None;
+                }
+                ((r + f) / 2, (f - r) / 2)
+            }
+            // parallel
+            _ => return None,
         };
+        Some((i64::try_from(x).ok()?, i64::try_from(y).ok()?))
+    }
+}
+
+/// The smallest and largest `x`, then the smallest and largest `y`, covered by something
+type Bounds = ((i64, i64), (i64, i64));
+
+/// The stretch of a line from `lo` to `hi` along it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Span {
+    line: Line,
+    lo: i64,
+    hi: i64,
+}
+
+impl Span {
+    fn contains(&self, p: (i64, i64)) -> bool {
+        self.line
+            .locate(p)
+            .is_some_and(|t| self.lo <= t && t <= self.hi)
+    }
+
+    fn bounds(&self) -> Bounds {
+        let ((x1, y1), (x2, y2)) = (self.line.point(self.lo), self.line.point(self.hi));
+        ((x1.min(x2), x1.max(x2)), (y1.min(y2), y1.max(y2)))
+    }
+}
 
-        x.into_iter().zip(y.into_iter()).for_each(|(x, y)| {
-            map.entry((x, y)).and_modify(|v| *v += 1).or_insert(1);
+impl Segment {
+    /// The span of line the segment covers
+    ///
+    /// Coordinates have to fit in an `i64`, and so does `x + y`, so that every line through every
+    /// point on the segment can be named.
+    fn span(&self) -> Result<Span, SegmentError> {
+        let err = || SegmentError(*self);
+        let signed = |(x, y): (u64, u64)| -> Result<(i64, i64), SegmentError> {
+            let (x, y) = (
+                i64::try_from(x).map_err(|_| err())?,
+                i64::try_from(y).map_err(|_| err())?,
+            );
+            x.checked_add(y).ok_or_else(err)?;
+            Ok((x, y))
+        };
+        let ((x1, y1), (x2, y2)) = (signed(self.from)?, signed(self.to)?);
+        let line = match self.orientation() {
+            Orientation::Horizontal => Line::Horizontal(y1),
+            Orientation::Vertical => Line::Vertical(x1),
+            Orientation::Diagonal if (x2 - x1).signum() == (y2 - y1).signum() => {
+                Line::Rising(x1 - y1)
+            }
+            Orientation::Diagonal => Line::Falling(x1 + y1),
+            Orientation::Other => return Err(err()),
+        };
+        let (t1, t2) = match line {
+            Line::Vertical(_) => (y1, y2),
+            _ => (x1, x2),
+        };
+        Ok(Span {
+            line,
+            lo: t1.min(t2),
+            hi: t1.max(t2),
         })
     }
 }
 
+/// Call `found` with each point where spans on different lines cross
+///
+/// We sweep from left to right, keeping the spans that reach the sweep's current `x`, so each span
+/// is only checked against the ones whose `x` and `y` ranges overlap its own.
+fn crossings<F: FnMut((i64, i64), &Span, &Span)>(spans: &[Span], mut found: F) {
+    let mut order = spans.iter().map(|s| (s.bounds(), s)).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|&(((x_lo, _), _), _)| x_lo);
+
+    let mut active: Vec<(Bounds, &Span)> = Vec::new();
+    for (bounds, span) in order {
+        let ((x_lo, _), (y_lo, y_hi)) = bounds;
+        active.retain(|&(((_, x_hi), _), _)| x_hi >= x_lo);
+        for &((_, (a_lo, a_hi)), other) in active.iter() {
+            if a_hi < y_lo || y_hi < a_lo {
+                continue;
+            }
+            if let Some(p) = span.line.cross(other.line) {
+                if span.contains(p) && other.contains(p) {
+                    found(p, other, span);
+                }
+            }
+        }
+        active.push((bounds, span));
+    }
+}
+
+/// Count overlapping points using only the segments' ends
+///
+/// Segments on the same line overlap along a stretch of it, which we find by sweeping along the
+/// line. Segments on different lines can only share the one point where the lines cross, which we
+/// find by sweeping across the map. Neither cares how long the segments are.
+fn count_overlaps_sweep(segs: &[Segment]) -> Result<usize, SegmentError> {
+    let spans = segs
+        .iter()
+        .map(Segment::span)
+        .collect::<Result<Vec<Span>, SegmentError>>()?;
+    let mut lines: HashMap<Line, Vec<&Span>> = HashMap::new();
+    for span in spans.iter() {
+        lines.entry(span.line).or_default().push(span);
+    }
+
+    // Sweep along each line, keeping track of how many segments cover the current point, to find
+    // the stretches covered more than once. Starts sort before ends at the same point, since the
+    // spans include both their ends.
+    let mut stretches = Vec::new();
+    for (&line, spans) in lines.iter() {
+        let mut events = spans
+            .iter()
+            .flat_map(|s| [(s.lo, false), (s.hi, true)])
+            .collect::<Vec<(i64, bool)>>();
+        events.sort_unstable();
+
+        let mut depth = 0;
+        let mut start = 0;
+        for (t, end) in events {
+            match (end, depth) {
+                (false, 1) => start = t,
+                (true, 2) => stretches.push(Span {
+                    line,
+                    lo: start,
+                    hi: t,
+                }),
+                _ => (),
+            }
+            depth += if end { -1 } else { 1 };
+        }
+    }
+    let mut overlaps: HashMap<Line, Vec<&Span>> = HashMap::new();
+    for stretch in stretches.iter() {
+        overlaps.entry(stretch.line).or_default().push(stretch);
+    }
+    let overlapped = |p: (i64, i64)| {
+        Line::through(p).iter().any(|line| {
+            overlaps
+                .get(line)
+                .is_some_and(|stretches| stretches.iter().any(|s| s.contains(p)))
+        })
+    };
+
+    let mut points = HashSet::new();
+    crossings(&spans, |p, _, _| {
+        if !overlapped(p) {
+            points.insert(p);
+        }
+    });
+
+    // A point where overlapping stretches on different lines cross has been counted once for each
+    // of those lines.
+    let mut shared: HashMap<(i64, i64), HashSet<Line>> = HashMap::new();
+    crossings(&stretches, |p, a, b| {
+        shared.entry(p).or_default().extend([a.line, b.line]);
+    });
+
+    let along = stretches
+        .iter()
+        .map(|s| (s.hi - s.lo) as usize + 1)
+        .sum::<usize>();
+    let repeats = shared.values().map(|lines| lines.len() - 1).sum::<usize>();
+    Ok(along - repeats + points.len())
+}
+
 #[cfg(test)]
 mod day5_tests {
 
     use super::*;
     use aoc2021::str_as_vec;
 
+    const TEST_DATA: &str = "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2";
+
+    fn seg(x1: u64, y1: u64, x2: u64, y2: u64) -> Segment {
+        Segment {
+            from: (x1, y1),
+            to: (x2, y2),
+        }
+    }
+
     #[test]
     fn test_parse_line() {
         let line = "0,9 -> 5,9";
-        assert_eq!(parse_line(line), ((0, 9), (5, 9)));
+        assert_eq!(parse_line(line), seg(0, 9, 5, 9));
+    }
+
+    #[test]
+    fn test_orientation() {
+        assert_eq!(seg(0, 9, 5, 9).orientation(), Orientation::Horizontal);
+        assert_eq!(seg(2, 2, 2, 1).orientation(), Orientation::Vertical);
+        assert_eq!(seg(8, 0, 0, 8).orientation(), Orientation::Diagonal);
+        assert_eq!(seg(0, 0, 2, 1).orientation(), Orientation::Other);
     }
 
     #[test]
     fn test_record_seg() {
         let mut map = HashMap::new();
 
-        record_seg(1, 1, 1, 3, &mut map);
+        record_seg(&seg(1, 1, 1, 3), &mut map).unwrap();
 
         assert_eq!(map.get(&(1, 1)), Some(&1));
         assert_eq!(map.get(&(1, 2)), Some(&1));
         assert_eq!(map.get(&(1, 3)), Some(&1));
 
         let mut map = HashMap::new();
-        record_seg(9, 7, 7, 7, &mut map);
+        record_seg(&seg(9, 7, 7, 7), &mut map).unwrap();
 
         assert_eq!(map.get(&(9, 7)), Some(&1));
         assert_eq!(map.get(&(8, 7)), Some(&1));
         assert_eq!(map.get(&(7, 7)), Some(&1));
 
         let mut map = HashMap::new();
-        record_seg(1, 1, 3, 3, &mut map);
+        record_seg(&seg(1, 1, 3, 3), &mut map).unwrap();
 
         assert_eq!(map.get(&(1, 1)), Some(&1));
         assert_eq!(map.get(&(2, 2)), Some(&1));
         assert_eq!(map.get(&(3, 3)), Some(&1));
 
         let mut map = HashMap::new();
-        record_seg(9, 7, 7, 9, &mut map);
+        record_seg(&seg(9, 7, 7, 9), &mut map).unwrap();
 
         assert_eq!(map.get(&(9, 7)), Some(&1));
         assert_eq!(map.get(&(8, 8)), Some(&1));
         assert_eq!(map.get(&(7, 9)), Some(&1));
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn test_bad_segment() {
+        let mut map = HashMap::new();
+        let bad = seg(0, 0, 2, 1);
+        assert_eq!(record_seg(&bad, &mut map), Err(SegmentError(bad)));
+        assert!(map.is_empty());
+
+        // filtered out segments are never drawn, so they can't fail
+        let segs = vec![seg(0, 0, 0, 2), bad];
+        assert_eq!(rasterise(&segs, |_| true), Err(SegmentError(bad)));
+        assert_eq!(
+            rasterise(&segs, Orientation::is_orthogonal).unwrap().len(),
+            3
+        );
     }
 
     #[test]
     fn test_case() {
-        let lines = str_as_vec(
-            "0,9 -> 5,9
-8,0 -> 0,8
-9,4 -> 3,4
-2,2 -> 2,1
-7,0 -> 7,4
-6,4 -> 2,0
-0,9 -> 2,9
-3,4 -> 1,4
-0,0 -> 8,8
-5,5 -> 8,2",
+        let segs = parse_lines(&str_as_vec(TEST_DATA));
+
+        let map = rasterise(&segs, Orientation::is_orthogonal).unwrap();
+        assert_eq!(count_overlaps(&map), 5);
+
+        let map = rasterise(&segs, |_| true).unwrap();
+        assert_eq!(count_overlaps(&map), 12);
+    }
+
+    #[test]
+    fn test_diagram() {
+        let segs = parse_lines(&str_as_vec(TEST_DATA));
+        let map = rasterise(&segs, |_| true).unwrap();
+        assert_eq!(
+            "1.1....11.\n\
+             .111...2..\n\
+             ..2.1.111.\n\
+             ...1.2.2..\n\
+             .112313211\n\
+             ...1.2....\n\
+             ..1...1...\n\
+             .1.....1..\n\
+             1.......1.\n\
+             222111....\n",
+            MapPrinter(&map).to_string()
         );
 
-        let mut map = HashMap::new();
+        let map = rasterise(&vec![seg(0, 0, 1, 0); 12], |_| true).unwrap();
+        assert_eq!("##\n", MapPrinter(&map).to_string());
+        assert_eq!("", MapPrinter(&Map::new()).to_string());
+    }
 
-        lines
-            .iter()
-            .map(|l| parse_line(l))
-            .for_each(|t| record_seg(t.0 .0, t.0 .1, t.1 .0, t.1 .1, &mut map));
+    #[test]
+    fn test_pgm() {
+        let map = rasterise(&[seg(0, 0, 2, 0), seg(1, 0, 1, 1)], |_| true).unwrap();
+        let mut expected = b"P5\n3 2\n2\n".to_vec();
+        expected.extend([1, 2, 1, 0, 1, 0]);
+        assert_eq!(expected, to_pgm(&map));
+
+        assert_eq!(b"P5\n0 0\n1\n".to_vec(), to_pgm(&Map::new()));
+    }
+
+    #[test]
+    fn test_engines() {
+        let segs = parse_lines(&str_as_vec(TEST_DATA));
+        for engine in [Engine::Sparse, Engine::Dense, Engine::Sweep] {
+            assert_eq!(
+                Ok(5),
+                count_overlaps_with(&segs, Orientation::is_orthogonal, engine)
+            );
+            assert_eq!(Ok(12), count_overlaps_with(&segs, |_| true, engine));
+        }
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        // lots of crossings, collinear overlaps, and points where several lines meet
+        let segs = (0..60u64)
+            .map(|i| {
+                let (a, b) = (i * 7 % 23, i * 11 % 19);
+                match i % 4 {
+                    0 => seg(a, b, a + b % 9, b),
+                    1 => seg(a, b, a, b + a % 7),
+                    2 => seg(a, b, a + b % 5, b + b % 5),
+                    _ => seg(a + b % 6, b, a, b + b % 6),
+                }
+            })
+            .collect::<Vec<Segment>>();
+        let expected = count_overlaps_with(&segs, |_| true, Engine::Sparse).unwrap();
+        assert!(expected > 0);
+        assert_eq!(
+            Ok(expected),
+            count_overlaps_with(&segs, |_| true, Engine::Dense)
+        );
+        assert_eq!(
+            Ok(expected),
+            count_overlaps_with(&segs, |_| true, Engine::Sweep)
+        );
+    }
 
-        println!("{:?}", map);
-        assert_eq!(map.values().filter(|&&v| v > 1).cloned().count(), 12);
+    #[test]
+    fn test_crossing_overlaps() {
+        // doubled horizontal, vertical, and diagonal lines all meeting at (5, 5)
+        let segs = vec![
+            seg(0, 5, 10, 5),
+            seg(2, 5, 8, 5),
+            seg(5, 0, 5, 10),
+            seg(5, 3, 5, 7),
+            seg(4, 4, 6, 6),
+            seg(3, 3, 7, 7),
+        ];
+        for engine in [Engine::Sparse, Engine::Dense, Engine::Sweep] {
+            assert_eq!(Ok(13), count_overlaps_with(&segs, |_| true, engine));
+        }
+    }
+
+    #[test]
+    fn test_huge_coordinates() {
+        // too big for an i64
+        let too_big = seg(0, u64::MAX, 5, u64::MAX);
+        assert_eq!(
+            Err(SegmentError(too_big)),
+            count_overlaps_with(&[too_big], |_| true, Engine::Sweep)
+        );
+        // fits, but x + y doesn't
+        let edge = i64::MAX as u64;
+        let too_big = seg(edge, 1, edge, 9);
+        assert_eq!(
+            Err(SegmentError(too_big)),
+            count_overlaps_with(&[too_big], |_| true, Engine::Sweep)
+        );
+        // right up against the limit is fine
+        let segs = [seg(edge - 5, 0, edge, 0), seg(edge - 5, 0, edge, 0)];
+        assert_eq!(Ok(6), count_overlaps_with(&segs, |_| true, Engine::Sweep));
+    }
+
+    #[test]
+    fn test_dense_too_big() {
+        let edge = i64::MAX as u64;
+        let far = seg(edge - 5, 0, edge, 0);
+        assert_eq!(
+            Err(SegmentError(far)),
+            count_overlaps_with(&[far, far], |_| true, Engine::Dense)
+        );
+        let too_big = seg(0, u64::MAX, 5, u64::MAX);
+        assert_eq!(
+            Err(SegmentError(too_big)),
+            count_overlaps_with(&[too_big], |_| true, Engine::Dense)
+        );
+        // each fits on its own, but not both together
+        let wide = seg(0, 0, 100_000, 0);
+        let tall = seg(0, 0, 0, 100_000);
+        assert_eq!(
+            Err(SegmentError(tall)),
+            count_overlaps_with(&[wide, tall], |_| true, Engine::Dense)
+        );
+        // the sweep doesn't need a grid at all
+        assert_eq!(
+            Ok(1),
+            count_overlaps_with(&[wide, tall], |_| true, Engine::Sweep)
+        );
+    }
+
+    #[test]
+    fn test_cross() {
+        assert_eq!(Some((2, 1)), Line::Rising(1).cross(Line::Falling(3)));
+        assert_eq!(None, Line::Rising(0).cross(Line::Falling(3)));
+        assert_eq!(None, Line::Horizontal(0).cross(Line::Horizontal(1)));
+    }
+
+    #[test]
+    fn test_long_segments() {
+        let segs = vec![
+            seg(0, 0, 4_000_000_000, 4_000_000_000),
+            seg(1_000_000_000, 1_000_000_000, 5_000_000_000, 5_000_000_000),
+            seg(0, 4_000_000_000, 4_000_000_000, 0),
+        ];
+        assert_eq!(
+            Ok(3_000_000_001),
+            count_overlaps_with(&segs, |_| true, Engine::Sweep)
+        );
     }
 }
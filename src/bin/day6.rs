@@ -1,35 +1,51 @@
 use aoc2021::lines_as_vec;
+use num_bigint::BigUint;
 use std::collections::VecDeque;
 
 fn main() {
+    // `day6 <days> [<rate> <juv>]` counts the fish after any number of days, and
+    // `day6 csv|json <days> [<rate> <juv>]` dumps every day's census up to then
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        let (format, args) = match args[0].as_str() {
+            "csv" | "json" => (Some(args[0].as_str()), &args[1..]),
+            _ => (None, &args[..]),
+        };
+        let nums = args
+            .iter()
+            .map(|a| a.parse::<u64>().expect("not a number"))
+            .collect::<Vec<u64>>();
+        let (days, model) = match nums[..] {
+            [days] => (days, Model::default()),
+            [days, rate, juv] => (days, Model::new(rate as usize, juv as usize)),
+            _ => panic!("usage: day6 [csv|json] <days> [<rate> <juv>]"),
+        };
+        let fish = get_initial_state(&lines_as_vec("input/day6.txt"));
+        let censuses = || model.history(&fish).take(days as usize + 1);
+        match format {
+            Some("csv") => print!("{}", to_csv(censuses())),
+            Some(_) => println!("{}", to_json(censuses())),
+            None => println!("{} fish after {} days", model.population(days, &fish), days),
+        }
+        return;
+    }
+
     println!("Part 1: {}", part1());
     println!("Part 2: {}", part2());
 }
 
-fn part1() -> usize {
+fn part1() -> BigUint {
     let data = lines_as_vec("input/day6.txt");
-    let st = get_initial_state(&data);
-
-    let mut world: Vec<Fish> = st.iter().map(|&s| Fish(s)).collect();
-    for _ in 0..80 {
-        let mut t = tick(&mut world);
-        world.append(&mut t);
-    }
+    let fish = get_initial_state(&data);
 
-    world.len()
+    Model::default().population(80, &fish)
 }
 
-fn part2() -> usize {
+fn part2() -> BigUint {
     let data = lines_as_vec("input/day6.txt");
     let fish = get_initial_state(&data);
 
-    let rate = 7; // fish reproduce after 7 days
-    let juv = 2; // fish need two extra days the first cycle
-
-    let days = 256;
-
-    fish_life(rate, juv, days, &fish) as usize
-
+    Model::default().population(256, &fish)
 }
 
 fn get_initial_state(lines: &[String]) -> Vec<i64> {
@@ -39,28 +55,237 @@ fn get_initial_state(lines: &[String]) -> Vec<i64> {
         .collect()
 }
 
-fn tick(world: &mut [Fish]) -> Vec<Fish> {
-    world.iter_mut().map(|fish| fish.tick()).flatten().collect()
+/// How lanternfish breed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Model {
+    /// Fish spawn every `rate` days
+    rate: usize,
+    /// New fish need `juv` extra days before they first spawn
+    juv: usize,
 }
 
-#[derive(Debug, Eq, PartialEq)]
-struct Fish(i64);
-
-impl Default for Fish {
+impl Default for Model {
     fn default() -> Self {
-        Fish(8)
+        Model::new(7, 2)
+    }
+}
+
+impl Model {
+    fn new(rate: usize, juv: usize) -> Self {
+        assert!(rate > 0, "fish must take at least a day to spawn");
+        Model { rate, juv }
+    }
+
+    /// How many different timer values a fish can have
+    fn ages(&self) -> usize {
+        self.rate + self.juv
+    }
+
+    /// The matrix taking one day's count of fish by timer to the next day's
+    fn day(&self) -> Matrix {
+        let n = self.ages();
+        let mut m = Matrix::zero(n);
+        for age in 1..n {
+            m.cells[(age - 1) * n + age] += 1u32;
+        }
+        // fish at zero reset to `rate - 1`, and their young start at the oldest timer
+        m.cells[(self.rate - 1) * n] += 1u32;
+        m.cells[(n - 1) * n] += 1u32;
+        m
+    }
+
+    /// Count the fish after `days`
+    ///
+    /// This takes `O(log days)` matrix products, so days in the millions are fine, although the
+    /// counts are hundreds of thousands of digits long by then.
+    fn population(&self, days: u64, fish: &[i64]) -> BigUint {
+        let counts = self
+            .counts(fish)
+            .into_iter()
+            .map(BigUint::from)
+            .collect::<Vec<_>>();
+        self.day().pow(days).apply(&counts).into_iter().sum()
+    }
+
+    /// Count the fish with each timer value
+    fn counts(&self, fish: &[i64]) -> Vec<u128> {
+        let mut counts = vec![0u128; self.ages()];
+        for &f in fish {
+            let age = usize::try_from(f)
+                .ok()
+                .filter(|&a| a < self.ages())
+                .expect("fish timer out of range");
+            counts[age] += 1;
+        }
+        counts
+    }
+
+    /// Every day's census, starting with the fish we were given
+    fn history(&self, fish: &[i64]) -> History {
+        History {
+            rate: self.rate,
+            day: 0,
+            world: Some(VecDeque::from(self.counts(fish))),
+        }
+    }
+}
+
+/// How many fish there are with each timer value on some day
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Census {
+    day: u64,
+    ages: Vec<u128>,
+}
+
+impl Census {
+    fn total(&self) -> u128 {
+        self.ages.iter().sum()
+    }
+}
+
+/// Day by day censuses of the fish, which end when there are too many fish to count
+struct History {
+    rate: usize,
+    day: u64,
+    /// Fish counts by timer value, which rotate round as the days pass
+    world: Option<VecDeque<u128>>,
+}
+
+impl Iterator for History {
+    type Item = Census;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let world = self.world.as_mut()?;
+        let census = Census {
+            day: self.day,
+            ages: world.iter().copied().collect(),
+        };
+        // a census only counts if its total fits too
+        census
+            .ages
+            .iter()
+            .try_fold(0u128, |acc, &n| acc.checked_add(n))?;
+
+        let spawned = world.pop_front().unwrap();
+        world.push_back(spawned);
+        match world[self.rate - 1].checked_add(spawned) {
+            Some(n) => world[self.rate - 1] = n,
+            None => self.world = None,
+        }
+        self.day += 1;
+        Some(census)
+    }
+}
+
+/// Dump censuses as CSV, one row per day
+fn to_csv(censuses: impl Iterator<Item = Census>) -> String {
+    let mut censuses = censuses.peekable();
+    let ages = censuses.peek().map_or(0, |c| c.ages.len());
+    let mut csv = ["day".to_owned(), "total".to_owned()]
+        .into_iter()
+        .chain((0..ages).map(|a| format!("age_{}", a)))
+        .collect::<Vec<_>>()
+        .join(",");
+    csv.push('\n');
+
+    for c in censuses {
+        let row = [c.day.to_string(), c.total().to_string()]
+            .into_iter()
+            .chain(c.ages.iter().map(|n| n.to_string()))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push_str(&row);
+        csv.push('\n');
     }
+    csv
+}
+
+/// Dump censuses as a JSON array of `{"day", "total", "ages"}` objects
+///
+/// Counts are written as plain numbers even when they're too big for a JavaScript number, so
+/// whatever reads this needs to cope with big integers to stay exact.
+fn to_json(censuses: impl Iterator<Item = Census>) -> String {
+    let objects = censuses
+        .map(|c| {
+            let ages = c
+                .ages
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(
+                "{{\"day\":{},\"total\":{},\"ages\":[{}]}}",
+                c.day,
+                c.total(),
+                ages
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", objects.join(","))
+}
+
+/// A square matrix of counts
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Matrix {
+    n: usize,
+    /// Row major
+    cells: Vec<BigUint>,
 }
 
-impl Fish {
-    fn tick(&mut self) -> Option<Self> {
-        if self.0 == 0 {
-            self.0 = 6;
-            Some(Fish::default())
-        } else {
-            self.0 -= 1;
-            None
+impl Matrix {
+    fn zero(n: usize) -> Self {
+        Matrix {
+            n,
+            cells: vec![BigUint::default(); n * n],
+        }
+    }
+
+    fn identity(n: usize) -> Self {
+        let mut m = Matrix::zero(n);
+        for i in 0..n {
+            m.cells[i * n + i] += 1u32;
+        }
+        m
+    }
+
+    fn mul(&self, other: &Matrix) -> Matrix {
+        let n = self.n;
+        let mut m = Matrix::zero(n);
+        for r in 0..n {
+            for c in 0..n {
+                m.cells[r * n + c] = (0..n)
+                    .map(|k| &self.cells[r * n + k] * &other.cells[k * n + c])
+                    .sum();
+            }
+        }
+        m
+    }
+
+    /// Raise the matrix to the power `e` by repeated squaring
+    fn pow(&self, mut e: u64) -> Matrix {
+        let mut result = Matrix::identity(self.n);
+        let mut square = self.clone();
+        while e > 0 {
+            if e & 1 == 1 {
+                result = result.mul(&square);
+            }
+            e >>= 1;
+            // the last squaring would be the most expensive, and we'd never use it
+            if e > 0 {
+                square = square.mul(&square);
+            }
         }
+        result
+    }
+
+    fn apply(&self, v: &[BigUint]) -> Vec<BigUint> {
+        (0..self.n)
+            .map(|r| {
+                (0..self.n)
+                    .map(|c| &self.cells[r * self.n + c] * &v[c])
+                    .sum()
+            })
+            .collect()
     }
 }
 
@@ -68,34 +293,150 @@ impl Fish {
 mod day6_tests {
     use super::*;
 
+    /// Simulate the fish a day at a time, to check the faster models against
+    fn fish_life(rate: i64, juv: i64, days: i64, fish: &[i64]) -> i64 {
+        let mut world = VecDeque::from(vec![0; (rate + juv) as usize]);
+
+        for &f in fish {
+            world[f as usize] += 1;
+        }
+
+        for _ in 0..days {
+            let spawned = world.pop_front().unwrap();
+            world.push_back(spawned);
+            world[rate as usize - 1] += spawned;
+        }
+
+        world.iter().sum()
+    }
+
+    fn big(n: u64) -> BigUint {
+        BigUint::from(n)
+    }
+
     #[test]
-    fn test_live() {
-        let mut f = vec![Fish(0)];
-        let r = tick(&mut f);
-        assert_eq!(f, vec![Fish(6)]);
-        assert_eq!(r, vec![Fish(8)]);
+    fn test_day() {
+        let m = Model::default().day();
+        let v = [1, 1, 2, 3, 4, 5, 6, 7, 8].map(big);
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 6, 7 + 1, 8, 1]
+                .into_iter()
+                .map(big)
+                .collect::<Vec<_>>(),
+            m.apply(&v)
+        );
     }
 
     #[test]
     fn test_case() {
         let test_data = vec!["3,4,3,1,2".to_owned()];
-        let st = get_initial_state(&test_data);
+        let fish = get_initial_state(&test_data);
+
+        assert_eq!(big(26), Model::default().population(18, &fish));
+        assert_eq!(big(5934), Model::default().population(80, &fish));
+        assert_eq!(big(26984457539), Model::default().population(256, &fish));
+        assert_eq!(big(5), Model::default().population(0, &fish));
+    }
+
+    #[test]
+    fn test_model_matches_deque() {
+        let test_data = vec!["3,4,3,1,2".to_owned()];
+        let fish = get_initial_state(&test_data);
 
-        let mut world: Vec<Fish> = st.iter().map(|&s| Fish(s)).collect();
-        for _ in 0..18 {
-            let mut t = tick(&mut world);
-            world.append(&mut t);
+        for (rate, juv) in [(7, 2), (3, 2), (5, 4), (2, 3)] {
+            let model = Model::new(rate, juv);
+            for days in 0..100 {
+                assert_eq!(
+                    big(fish_life(rate as i64, juv as i64, days as i64, &fish) as u64),
+                    model.population(days, &fish)
+                );
+            }
         }
+    }
 
-        assert_eq!(26, world.len());
+    #[test]
+    fn test_history() {
+        let fish = [3, 4, 3, 1, 2];
+        let days = Model::default().history(&fish).take(3).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Census {
+                    day: 0,
+                    ages: vec![0, 1, 1, 2, 1, 0, 0, 0, 0]
+                },
+                Census {
+                    day: 1,
+                    ages: vec![1, 1, 2, 1, 0, 0, 0, 0, 0]
+                },
+                Census {
+                    day: 2,
+                    ages: vec![1, 2, 1, 0, 0, 0, 1, 0, 1]
+                },
+            ],
+            days
+        );
+
+        // the history stops rather than overflow
+        assert_eq!(128, Model::new(1, 0).history(&[0]).count());
+    }
 
-        let mut world: Vec<Fish> = st.iter().map(|&s| Fish(s)).collect();
-        for _ in 0..80 {
-            let mut t = tick(&mut world);
-            world.append(&mut t);
+    #[test]
+    fn test_history_matches_deque() {
+        let fish = [3, 4, 3, 1, 2];
+        for (rate, juv) in [(7, 2), (3, 2), (5, 4), (2, 3)] {
+            let totals = Model::new(rate, juv)
+                .history(&fish)
+                .take(100)
+                .map(|c| c.total())
+                .collect::<Vec<u128>>();
+            let expected = (0..100)
+                .map(|days| fish_life(rate as i64, juv as i64, days, &fish) as u128)
+                .collect::<Vec<u128>>();
+            assert_eq!(expected, totals);
         }
+        assert_eq!(
+            Some(26984457539),
+            Model::default().history(&fish).nth(256).map(|c| c.total())
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let history = || Model::new(2, 1).history(&[0]).take(3);
+        assert_eq!(
+            "day,total,age_0,age_1,age_2\n\
+             0,1,1,0,0\n\
+             1,2,0,1,1\n\
+             2,2,1,1,0\n",
+            to_csv(history())
+        );
+        assert_eq!(
+            "[{\"day\":0,\"total\":1,\"ages\":[1,0,0]},\
+             {\"day\":1,\"total\":2,\"ages\":[0,1,1]},\
+             {\"day\":2,\"total\":2,\"ages\":[1,1,0]}]",
+            to_json(history())
+        );
+        assert_eq!("day,total\n", to_csv(std::iter::empty()));
+        assert_eq!("[]", to_json(std::iter::empty()));
+    }
+
+    #[test]
+    fn test_many_days() {
+        let fish = [3, 4, 3, 1, 2];
+
+        // well past where a u128 would overflow
+        let n = Model::default().population(1000, &fish);
+        assert_eq!("379589061144698259131825683795505058481", n.to_string());
+
+        let n = Model::default().population(100_000, &fish);
+        assert_eq!(3785, n.to_string().len());
+        assert_eq!(big(663433029), n % big(1_000_000_007));
 
-        assert_eq!(5934, world.len());
+        // with no juvenile delay, a fish doubles its family every day
+        assert_eq!(
+            big(1) << 5_000_000u32,
+            Model::new(1, 0).population(5_000_000, &[0])
+        );
     }
 
     #[test]
@@ -117,19 +458,3 @@ mod day6_tests {
         assert_eq!(26984457539, fish_life(rate, juv, days, &fish));
     }
 }
-
-fn fish_life(rate: i64, juv: i64, days: i64, fish: &[i64]) -> i64 {
-    let mut world = VecDeque::from(vec![0; (rate + juv) as usize]);
-
-    for &f in fish {
-        world[f as usize] += 1;
-    }
-
-    for _ in 0..days {
-        let spawned = world.pop_front().unwrap();
-        world.push_back(spawned);
-        world[rate as usize - 1] += spawned;
-    }
-
-    world.iter().sum()
-}
@@ -1,31 +1,100 @@
 use aoc2021::{get_num_list, lines_as_vec};
 
 fn main() {
-    println!("Solution: {}", solve());
+    let crabs = get_num_list::<i64>(&lines_as_vec("input/day7.txt"));
+
+    // `day7 <k>` also tries a cost of `d^k` fuel to move `d` steps
+    if let Some(k) = std::env::args().nth(1) {
+        let k = k.parse::<u32>().expect("not a number");
+        let (pos, fuel) = Convex(|d: i64| d.pow(k)).best(&crabs).unwrap();
+        println!("d^{}: {} (position {})", k, fuel, pos);
+    }
+
+    let (pos, fuel) = Linear.best(&crabs).unwrap();
+    println!("Part 1: {} (position {})", fuel, pos);
+    let (pos, fuel) = Triangular.best(&crabs).unwrap();
+    println!("Part 2: {} (position {})", fuel, pos);
 }
 
-fn solve() -> i64 {
-    // okay, listen. so for part 1, I wrote this to use the median. and it worked. cool.
-    //
-    // but it didn't work for part 2. but I had already written some test code that just did a
-    // search. because why not brute-force it, right? turns out the totally naive, brute-force
-    // solution runs in some tiny fraction of a second when compiled in release mode. so fuck it,
-    // we'll just brute-force it. this *should* use the mean to calculate it directly, but...
-    // whatever.
-
-    let mut initial_state = get_num_list(&lines_as_vec("input/day7.txt"));
-    initial_state.sort_unstable();
-    (*initial_state.first().unwrap()..=*initial_state.as_slice().last().unwrap())
-        .map(|dest| calc_fuel_for(&initial_state, dest))
-        .min()
-        .unwrap()
+/// How much fuel it takes a crab to move, which should be convex in the distance moved
+///
+/// Then the total fuel is convex in where the crabs line up too, so the cheapest place can be
+/// found by searching for where the total stops going down.
+trait FuelCost {
+    /// The fuel it takes one crab to move `d` steps
+    fn cost(&self, d: i64) -> i64;
+
+    /// The fuel it takes all the crabs to move to `dest`
+    fn total(&self, posns: &[i64], dest: i64) -> i64 {
+        posns.iter().map(|&p| self.cost((p - dest).abs())).sum()
+    }
+
+    /// The cheapest place for the crabs to line up, and the fuel it takes to get there
+    fn best(&self, posns: &[i64]) -> Option<(i64, i64)> {
+        let (mut lo, mut hi) = (*posns.iter().min()?, *posns.iter().max()?);
+        // find the first place where moving one further right doesn't save anything
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.total(posns, mid + 1) < self.total(posns, mid) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Some((lo, self.total(posns, lo)))
+    }
 }
 
-fn calc_fuel_for(posns: &[i64], dest: i64) -> i64 {
-    posns
-        .iter()
-        .map(|&p| (1..=(p - dest).abs()).sum::<i64>())
-        .sum()
+/// Each step costs one fuel, so the median is cheapest
+struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, d: i64) -> i64 {
+        d
+    }
+
+    fn best(&self, posns: &[i64]) -> Option<(i64, i64)> {
+        if posns.is_empty() {
+            return None;
+        }
+        let mut posns = posns.to_vec();
+        let mid = (posns.len() - 1) / 2;
+        let median = *posns.select_nth_unstable(mid).1;
+        Some((median, self.total(&posns, median)))
+    }
+}
+
+/// Each step costs one more fuel than the last
+///
+/// The cheapest place is always within half a step of the mean, so it's one of the whole numbers
+/// either side of it.
+struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, d: i64) -> i64 {
+        d * (d + 1) / 2
+    }
+
+    fn best(&self, posns: &[i64]) -> Option<(i64, i64)> {
+        if posns.is_empty() {
+            return None;
+        }
+        let n = posns.len() as i64;
+        let floor = posns.iter().sum::<i64>().div_euclid(n);
+        [floor, floor + 1]
+            .into_iter()
+            .map(|dest| (dest, self.total(posns, dest)))
+            .min_by_key(|&(dest, fuel)| (fuel, dest))
+    }
+}
+
+/// Any other convex cost, worked out by the closure
+struct Convex<F>(F);
+
+impl<F: Fn(i64) -> i64> FuelCost for Convex<F> {
+    fn cost(&self, d: i64) -> i64 {
+        (self.0)(d)
+    }
 }
 
 #[cfg(test)]
@@ -33,17 +102,58 @@ mod day7_tests {
 
     use super::*;
 
+    fn test_data() -> Vec<i64> {
+        get_num_list::<i64>(&[String::from("16,1,2,0,4,2,7,1,2,14")])
+    }
+
+    /// Try every position between the outermost crabs
+    fn brute_force(cost: &impl FuelCost, posns: &[i64]) -> i64 {
+        let (lo, hi) = (*posns.iter().min().unwrap(), *posns.iter().max().unwrap());
+        (lo..=hi).map(|dest| cost.total(posns, dest)).min().unwrap()
+    }
+
     #[test]
     fn test_case() {
-        let test_data = vec![String::from("16,1,2,0,4,2,7,1,2,14")];
-        let initial_state = get_num_list::<i64>(&test_data);
+        let crabs = test_data();
+
+        assert_eq!(Some((2, 37)), Linear.best(&crabs));
+        assert_eq!(41, Linear.total(&crabs, 1));
+        assert_eq!(Some((5, 168)), Triangular.best(&crabs));
+        assert_eq!(206, Triangular.total(&crabs, 2));
+    }
 
-        let c = (initial_state.iter().cloned().min().unwrap()
-            ..=initial_state.iter().cloned().max().unwrap())
-            .map(|dest| calc_fuel_for(&initial_state, dest))
-            .min()
-            .unwrap();
+    #[test]
+    fn test_convex() {
+        let crabs = test_data();
 
-        assert_eq!(c, 168);
+        assert_eq!(Linear.best(&crabs), Convex(|d| d).best(&crabs));
+        assert_eq!(
+            Triangular.best(&crabs),
+            Convex(|d| d * (d + 1) / 2).best(&crabs)
+        );
+        let square = Convex(|d| d * d);
+        assert_eq!(brute_force(&square, &crabs), square.best(&crabs).unwrap().1);
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        let sets = [
+            vec![0],
+            vec![3, 3, 3],
+            vec![-5, 0, 100],
+            vec![1, 2],
+            vec![0, 0, 0, 0, 9],
+            vec![-7, -3, -2, 40, 41, 1000],
+        ];
+        for crabs in sets {
+            assert_eq!(brute_force(&Linear, &crabs), Linear.best(&crabs).unwrap().1);
+            assert_eq!(
+                brute_force(&Triangular, &crabs),
+                Triangular.best(&crabs).unwrap().1
+            );
+        }
+        assert_eq!(None, Linear.best(&[]));
+        assert_eq!(None, Triangular.best(&[]));
+        assert_eq!(None, Convex(|d| d).best(&[]));
     }
 }
@@ -1,23 +1,130 @@
 use aoc2021::lines_as_vec;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 fn main() {
+    // `day8 generic` decodes part 2 with the constraint solver instead
+    let generic = std::env::args().nth(1).as_deref() == Some("generic");
+
     println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+    println!("Part 2: {}", part2(generic));
 }
 
 fn part1() -> usize {
     let data = lines_as_vec("input/day8.txt");
+    let display = Display::seven_segment();
 
     data.iter()
-        .flat_map(|l| {
-            l.split(' ').map(|w| w.to_string()).collect::<Vec<String>>()[11..=14].to_vec()
-        })
-        .filter(|w| matches!(w.len(), 2 | 3 | 4 | 7))
+        .flat_map(|l| parse_line(l).unwrap().1)
+        .filter(|w| display.unique_len(w.len()))
         .count()
 }
 
+fn part2(generic: bool) -> usize {
+    let data = lines_as_vec("input/day8.txt");
+    if !generic {
+        return decode_all(&data).unwrap();
+    }
+    let display = Display::seven_segment();
+
+    data.iter()
+        .map(|l| match decode_line(&display, l) {
+            Ok(n) => n,
+            Err(e) => panic!("{}: {}", l, e),
+        })
+        .sum()
+}
+
+/// The segments a display lights up for each of its digits
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Display {
+    /// Every segment the display has
+    segments: BTreeSet<char>,
+    /// The segments lit for each digit, in order of value
+    digits: Vec<BTreeSet<char>>,
+}
+
+impl Display {
+    /// A display lighting `digits[n]` for the digit `n`
+    fn new(digits: &[&str]) -> Self {
+        let digits = digits
+            .iter()
+            .map(|d| d.chars().collect::<BTreeSet<char>>())
+            .collect::<Vec<_>>();
+        let distinct = digits.iter().collect::<HashSet<_>>();
+        assert_eq!(
+            distinct.len(),
+            digits.len(),
+            "every digit must look different"
+        );
+        Display {
+            segments: digits.iter().flatten().copied().collect(),
+            digits,
+        }
+    }
+
+    /// The usual seven segments, named `A` to `G` from the top, going clockwise, then the middle
+    fn seven_segment() -> Self {
+        Display::new(&[
+            "ABCEFG", "CF", "ACDEG", "ACDFG", "BCDF", "ABDFG", "ABDEFG", "ACF", "ABCDEFG", "ABCDFG",
+        ])
+    }
+
+    /// Whether only one digit lights up `n` segments, so it can be recognised without decoding
+    fn unique_len(&self, n: usize) -> bool {
+        self.digits.iter().filter(|d| d.len() == n).count() == 1
+    }
+}
+
+/// Errors decoding the signals on a line
+#[derive(Debug, PartialEq, Eq)]
+enum DecodeError {
+    /// There was no `|` between the signal patterns and the output
+    MissingOutput,
+    /// There are more wires than the display has segments
+    TooManyWires(usize),
+    /// No wiring lights up digits for every pattern
+    Inconsistent,
+    /// More than one wiring fits the patterns, such as these two
+    Ambiguous(Mapping, Mapping),
+    /// The output uses a pattern that isn't a digit under the wiring
+    UnknownPattern(String),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            DecodeError::MissingOutput => write!(f, "no `|` before the output"),
+            DecodeError::TooManyWires(n) => {
+                write!(f, "{} wires is more than there are segments", n)
+            }
+            DecodeError::Inconsistent => write!(f, "no wiring fits every pattern"),
+            DecodeError::Ambiguous(..) => write!(f, "more than one wiring fits the patterns"),
+            DecodeError::UnknownPattern(p) => write!(f, "`{}` isn't a digit", p),
+        }
+    }
+}
+
+/// Split a line into its signal patterns and its output
+fn parse_line(line: &str) -> Result<(Vec<&str>, Vec<&str>), DecodeError> {
+    let (signals, output) = line.split_once('|').ok_or(DecodeError::MissingOutput)?;
+    Ok((
+        signals.split_whitespace().collect(),
+        output.split_whitespace().collect(),
+    ))
+}
+
+/// Decode a line's output, reading its digits as a decimal number
+fn decode_line(display: &Display, line: &str) -> Result<usize, DecodeError> {
+    let (signals, output) = parse_line(line)?;
+    // the output digits are patterns too, which might pin down something the signals didn't
+    let mapping = solve(display, &[&signals[..], &output[..]].concat())?;
+    output
+        .iter()
+        .try_fold(0, |acc, w| Ok(acc * 10 + mapping.light(display, w)?))
+}
+
+/// Which wires could still be driving each segment
 #[derive(Debug, Clone)]
 struct Wiring(HashMap<char, HashSet<char>>);
 
@@ -27,168 +134,476 @@ impl Wiring {
             .insert(idx, self.0[&idx].intersection(upd).cloned().collect());
     }
 
-    fn light(&self, pins: &str) -> u8 {
-        let t = self.finalize();
-        let mut output: Vec<char> = pins.chars().map(|p| t[&p]).collect();
-        output.sort_unstable();
-        match output.as_slice() {
-            ['A', 'B', 'C', 'E', 'F', 'G'] => 0,
-            ['C', 'F'] => 1,
-            ['A', 'C', 'D', 'E', 'G'] => 2,
-            ['A', 'C', 'D', 'F', 'G'] => 3,
-            ['B', 'C', 'D', 'F'] => 4,
-            ['A', 'B', 'D', 'F', 'G'] => 5,
-            ['A', 'B', 'D', 'E', 'F', 'G'] => 6,
-            ['A', 'C', 'F'] => 7,
-            ['A', 'B', 'C', 'D', 'E', 'F', 'G'] => 8,
-            ['A', 'B', 'C', 'D', 'F', 'G'] => 9,
-            _ => panic!("bad pin: {:?}", output),
-        }
-    }
-
-    fn finalize(&self) -> HashMap<char, char> {
+    /// Take away wires from a segment
+    fn exclude(&mut self, idx: char, wires: &HashSet<char>) {
         self.0
-            .iter()
-            .map(|(output, input)| (*(input.iter().next().unwrap()), *output))
-            .collect()
+            .insert(idx, self.0[&idx].difference(wires).cloned().collect());
     }
-}
 
-impl Default for Wiring {
-    fn default() -> Self {
-        Self(HashMap::from([
-            ('A', HashSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-            ('B', HashSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-            ('C', HashSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-            ('D', HashSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-            ('E', HashSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-            ('F', HashSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-            ('G', HashSet::from(['a', 'b', 'c', 'd', 'e', 'f', 'g'])),
-        ]))
+    /// Once a segment is down to a single wire, no other segment can use it
+    ///
+    /// Returns `false` if some segment is left with no wires at all.
+    fn propagate(&mut self) -> bool {
+        let mut settled = HashSet::new();
+        loop {
+            let fixed = self
+                .0
+                .iter()
+                .filter(|(s, w)| w.len() == 1 && !settled.contains(*s))
+                .map(|(&s, w)| (s, *w.iter().next().unwrap()))
+                .collect::<Vec<_>>();
+            if fixed.is_empty() {
+                return self.0.values().all(|w| !w.is_empty());
+            }
+            for (seg, wire) in fixed {
+                settled.insert(seg);
+                for (_, w) in self.0.iter_mut().filter(|(&s, _)| s != seg) {
+                    w.remove(&wire);
+                }
+            }
+        }
     }
-}
 
-fn part2() -> u32 {
-    let data = lines_as_vec("input/day8.txt");
+    /// Add one-to-one choices of wire for each segment that are still possible to `found`, until
+    /// it holds `limit` of them
+    fn mappings(&self, found: &mut Vec<Mapping>, limit: usize) {
+        fn pick(
+            segs: &[(char, &HashSet<char>)],
+            used: &mut Vec<(char, char)>,
+            found: &mut Vec<Mapping>,
+            limit: usize,
+        ) {
+            match segs.split_first() {
+                None => found.push(Mapping(used.iter().copied().collect())),
+                Some(((seg, wires), rest)) => {
+                    let mut wires = wires.iter().collect::<Vec<_>>();
+                    wires.sort_unstable();
+                    for &w in wires {
+                        if found.len() >= limit {
+                            return;
+                        }
+                        if used.iter().all(|&(u, _)| u != w) {
+                            used.push((w, *seg));
+                            pick(rest, used, found, limit);
+                            used.pop();
+                        }
+                    }
+                }
+            }
+        }
 
-    data.iter()
-        .map(|line| {
-            let words: Vec<String> = line.split(' ').map(|w| w.to_string()).collect();
+        let mut segs = self.0.iter().map(|(&s, w)| (s, w)).collect::<Vec<_>>();
+        segs.sort_unstable_by_key(|&(s, w)| (w.len(), s));
+        pick(&segs, &mut Vec::new(), found, limit);
+    }
+}
 
-            let solution = solve_line(&words[0..=10]);
+/// Which segment each wire drives
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Mapping(HashMap<char, char>);
 
-            words[11..].iter().rev().enumerate().fold(0, |acc, (i, w)| {
-                acc + solution.light(w) as u32 * 10u32.pow(i as u32)
-            })
-        })
-        .sum()
+impl Mapping {
+    /// The digit lit up by a pattern of wires
+    fn light(&self, display: &Display, pins: &str) -> Result<usize, DecodeError> {
+        let unknown = || DecodeError::UnknownPattern(pins.to_string());
+        let lit = pins
+            .chars()
+            .map(|p| self.0.get(&p).copied())
+            .collect::<Option<BTreeSet<char>>>()
+            .ok_or_else(unknown)?;
+        display
+            .digits
+            .iter()
+            .position(|d| *d == lit)
+            .ok_or_else(unknown)
+    }
 }
 
-fn solve_line(data: &[String]) -> Wiring {
-    let mut solution = Wiring::default();
-
-    // get the number 1
-    let one = data
+/// Work out which wire drives which segment, from the patterns seen on them
+///
+/// Each distinct pattern has to be one of the display's digits, and no two patterns can be the
+/// same digit. We try each digit a pattern could be, narrowing down the wires each segment could
+/// use, and backtrack when a segment runs out. We stop as soon as we've found a second wiring,
+/// since by then we know the line is ambiguous.
+fn solve(display: &Display, signals: &[&str]) -> Result<Mapping, DecodeError> {
+    let patterns = signals
         .iter()
-        .find(|d| d.len() == 2)
-        .unwrap()
-        .chars()
-        .collect::<Vec<char>>();
-
-    // we can find signal A right away by comparing a 1 with a 7
-    solution.whittle(
-        'A',
-        &HashSet::from_iter(
-            data.iter()
-                .find(|d| d.len() == 3)
-                .unwrap()
-                .chars()
-                .filter(|c| !one.as_slice().contains(c)),
-        ),
-    );
-
-    // C or F is what's shared between 1 and 7
-    let c_or_f: HashSet<char> = one
+        .map(|s| s.chars().collect::<BTreeSet<char>>())
+        .collect::<BTreeSet<_>>();
+    let wires = patterns
         .iter()
+        .flatten()
         .copied()
-        .filter(|c| !solution.0[&'A'].contains(c))
-        .collect();
-    solution.whittle('C', &c_or_f);
-    solution.whittle('F', &c_or_f);
-
-    // B and D are what we know aren't C or F in the digit 4
-    let b_or_d: HashSet<char> = HashSet::from_iter(
-        data.iter()
-            .find(|d| d.len() == 4)
-            .unwrap()
-            .chars()
-            .filter(|s| !solution.0[&'C'].contains(s) && !solution.0[&'F'].contains(s)),
-    );
-    solution.whittle('B', &b_or_d);
-    solution.whittle('D', &b_or_d);
+        .collect::<HashSet<char>>();
+    if wires.len() > display.segments.len() {
+        return Err(DecodeError::TooManyWires(wires.len()));
+    }
 
-    // 0, 6, and 9 all share six signals
-    let mut zero_six_or_nine: Vec<String> = data.iter().filter(|d| d.len() == 6).cloned().collect();
-    zero_six_or_nine.dedup();
+    let mut wiring = Wiring(
+        display
+            .segments
+            .iter()
+            .map(|&s| (s, wires.clone()))
+            .collect(),
+    );
+    // wires we've never seen could be on any segment, so we can only tell if there aren't any
+    if wires.len() < display.segments.len() {
+        let unseen = ('a'..='z')
+            .chain('A'..='Z')
+            .filter(|c| !wires.contains(c))
+            .take(display.segments.len() - wires.len());
+        for s in display.segments.iter() {
+            wiring.0.get_mut(s).unwrap().extend(unseen.clone());
+        }
+    }
 
-    // 0, 6, and 9 overlap in A, B, F, G, but we already know what A is, so we can skip that
-    let b_f_g: HashSet<char> = zero_six_or_nine
-        .iter()
-        .map(|n: &String| HashSet::from_iter(n.chars())) // turn each String into a HashSet of chars
-        .reduce(|acc: HashSet<char>, n: HashSet<char>| {
-            acc.intersection(&n).cloned().collect::<HashSet<_>>() // store only the intersection of all of them
-        })
-        .unwrap()
+    // try the patterns with the fewest possible digits first
+    let mut patterns = patterns
         .into_iter()
-        .filter(|c| !solution.0[&'A'].contains(c))
-        .collect();
-    solution.whittle('B', &b_f_g);
-    solution.whittle('F', &b_f_g);
-    solution.whittle('G', &b_f_g);
-
-    // 2, 3, and 5 all share five signals
-    let mut two_three_or_five: Vec<String> =
-        data.iter().filter(|d| d.len() == 5).cloned().collect();
-    two_three_or_five.dedup();
-
-    // 2, 3, and 5 overlap in A, D, G. as before, we know what A is already
-    let d_g: HashSet<char> = two_three_or_five
-        .iter()
-        .map(|n: &String| HashSet::from_iter(n.chars())) // turn each String into a HashSet of chars
-        .reduce(|acc: HashSet<char>, n: HashSet<char>| {
-            acc.intersection(&n).cloned().collect::<HashSet<_>>() // store only the intersection of all of them
+        .map(|p| {
+            let wires = p.into_iter().collect::<HashSet<char>>();
+            let n = display
+                .digits
+                .iter()
+                .filter(|d| d.len() == wires.len())
+                .count();
+            (n, wires)
         })
-        .unwrap()
-        .into_iter()
-        .filter(|c| !solution.0[&'A'].contains(c))
-        .collect();
-
-    solution.whittle('D', &d_g);
-    solution.whittle('G', &d_g);
-
-    // that gives us all but C, which cannot be F
-    solution.0.insert(
-        'C',
-        solution.0[&'C']
-            .difference(&solution.0[&'F'])
-            .cloned()
-            .collect(),
+        .collect::<Vec<_>>();
+    patterns.sort_by_key(|(n, _)| *n);
+    let patterns = patterns.into_iter().map(|(_, p)| p).collect::<Vec<_>>();
+
+    let mut found = Vec::new();
+    search(
+        display,
+        &patterns,
+        &mut vec![false; display.digits.len()],
+        wiring,
+        &mut found,
     );
+    let second = found.pop();
+    match (found.pop(), second) {
+        (Some(a), Some(b)) => Err(DecodeError::Ambiguous(a, b)),
+        (None, Some(m)) => Ok(m),
+        _ => Err(DecodeError::Inconsistent),
+    }
+}
 
-    // and E is not in anything else
-    let everything_else = ['A', 'B', 'C', 'D', 'F', 'G']
-        .iter()
-        .fold(HashSet::new(), |acc, idx| &acc | &solution.0[idx]);
+/// Match each of `patterns` to an unused digit, collecting wirings that fit until there are two
+fn search(
+    display: &Display,
+    patterns: &[HashSet<char>],
+    used: &mut Vec<bool>,
+    wiring: Wiring,
+    found: &mut Vec<Mapping>,
+) {
+    let (pattern, rest) = match patterns.split_first() {
+        Some(p) => p,
+        None => {
+            wiring.mappings(found, 2);
+            return;
+        }
+    };
 
-    solution.0.insert(
-        'E',
-        solution.0[&'E']
-            .difference(&everything_else)
-            .cloned()
-            .collect(),
-    );
+    for (i, digit) in display.digits.iter().enumerate() {
+        if used[i] || digit.len() != pattern.len() {
+            continue;
+        }
+        // the segments in the digit must be driven by the pattern's wires, and the rest mustn't
+        let mut w = wiring.clone();
+        for seg in display.segments.iter() {
+            if digit.contains(seg) {
+                w.whittle(*seg, pattern);
+            } else {
+                w.exclude(*seg, pattern);
+            }
+        }
+        if w.propagate() {
+            used[i] = true;
+            search(display, rest, used, w, found);
+            used[i] = false;
+            if found.len() >= 2 {
+                return;
+            }
+        }
+    }
+}
+
+/// A pattern of wires as bits, with `a` the lowest
+fn mask(pattern: &str) -> Result<u8, DecodeError> {
+    pattern.bytes().try_fold(0, |m, b| match b {
+        b'a'..=b'g' => Ok(m | 1 << (b - b'a')),
+        _ => Err(DecodeError::UnknownPattern(pattern.to_string())),
+    })
+}
+
+/// The digit each of the 128 possible patterns lights up on a standard seven segment display
+struct Table([Option<u8>; 128]);
+
+impl Table {
+    /// Work out the digits from the ten distinct patterns seen on a line
+    ///
+    /// 1, 4, 7 and 8 are the only digits lighting up two, four, three and seven segments. The
+    /// others are told apart by how many of their segments they share with 1 and with 4.
+    fn new(signals: &[u8]) -> Result<Self, DecodeError> {
+        let find = |n| {
+            let mut found = signals.iter().filter(|p| p.count_ones() == n);
+            match (found.next(), found.next()) {
+                (Some(&p), None) => Ok(p),
+                _ => Err(DecodeError::Inconsistent),
+            }
+        };
+        let (one, four) = (find(2)?, find(4)?);
+
+        let mut table = [None; 128];
+        let mut seen = 0u16;
+        for &p in signals {
+            let digit = match (
+                p.count_ones(),
+                (p & one).count_ones(),
+                (p & four).count_ones(),
+            ) {
+                (2, _, _) => 1,
+                (3, _, _) => 7,
+                (4, _, _) => 4,
+                (7, _, _) => 8,
+                (5, 2, _) => 3,
+                (5, 1, 3) => 5,
+                (5, 1, 2) => 2,
+                (6, 1, _) => 6,
+                (6, 2, 4) => 9,
+                (6, 2, 3) => 0,
+                _ => return Err(DecodeError::Inconsistent),
+            };
+            if seen & 1 << digit != 0 {
+                return Err(DecodeError::Inconsistent);
+            }
+            seen |= 1 << digit;
+            table[p as usize] = Some(digit);
+        }
+        match seen {
+            0x3ff => Ok(Table(table)),
+            _ => Err(DecodeError::Inconsistent),
+        }
+    }
+}
+
+/// Decode every line's output and add them up, using bitmasks rather than the constraint solver
+///
+/// Each line is only split up once, and nothing gets allocated unless there's an error.
+fn decode_all<S: AsRef<str>>(lines: &[S]) -> Result<usize, DecodeError> {
+    lines.iter().try_fold(0, |total, line| {
+        let (signals, output) = line
+            .as_ref()
+            .split_once('|')
+            .ok_or(DecodeError::MissingOutput)?;
+
+        let mut patterns = [0u8; 10];
+        let mut n = 0;
+        for w in signals.split_whitespace() {
+            let p = mask(w)?;
+            if !patterns[..n].contains(&p) {
+                *patterns.get_mut(n).ok_or(DecodeError::Inconsistent)? = p;
+                n += 1;
+            }
+        }
+        let table = Table::new(&patterns[..n])?;
 
-    solution
+        let value = output.split_whitespace().try_fold(0, |acc, w| {
+            let digit = table.0[mask(w)? as usize]
+                .ok_or_else(|| DecodeError::UnknownPattern(w.to_string()))?;
+            Ok(acc * 10 + digit as usize)
+        })?;
+        Ok(total + value)
+    })
 }
 
-// hah no tests today
+#[cfg(test)]
+mod day8_tests {
+
+    use aoc2021::str_as_vec;
+
+    use super::*;
+
+    const TEST_DATA: &str =
+        "be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
+edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
+fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
+fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
+aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
+fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
+dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
+bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
+egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
+gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce";
+
+    #[test]
+    fn test_solve() {
+        let line =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+        let display = Display::seven_segment();
+        let (signals, _) = parse_line(line).unwrap();
+        let mapping = solve(&display, &signals).unwrap();
+
+        assert_eq!(
+            Mapping(HashMap::from([
+                ('d', 'A'),
+                ('e', 'B'),
+                ('a', 'C'),
+                ('f', 'D'),
+                ('g', 'E'),
+                ('b', 'F'),
+                ('c', 'G'),
+            ])),
+            mapping
+        );
+        assert_eq!(Ok(5353), decode_line(&display, line));
+    }
+
+    #[test]
+    fn test_case() {
+        let display = Display::seven_segment();
+        let lines = str_as_vec(TEST_DATA);
+
+        let easy = lines
+            .iter()
+            .flat_map(|l| parse_line(l).unwrap().1)
+            .filter(|w| display.unique_len(w.len()))
+            .count();
+        assert_eq!(26, easy);
+
+        let decoded = lines
+            .iter()
+            .map(|l| decode_line(&display, l))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            vec![8394, 9781, 1197, 9361, 4873, 8418, 4548, 1625, 8717, 4315],
+            decoded
+        );
+        assert_eq!(61229, decoded.iter().sum::<usize>());
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let lines = str_as_vec(TEST_DATA);
+        assert_eq!(Ok(61229), decode_all(&lines));
+        for line in lines {
+            assert_eq!(
+                decode_line(&Display::seven_segment(), &line),
+                decode_all(&[line])
+            );
+        }
+        assert_eq!(Ok(0), decode_all::<&str>(&[]));
+    }
+
+    #[test]
+    fn test_table() {
+        let signals = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab"
+            .split(' ')
+            .map(|w| mask(w).unwrap())
+            .collect::<Vec<u8>>();
+        let table = Table::new(&signals).unwrap();
+        assert_eq!(Some(8), table.0[0x7f]);
+        assert_eq!(Some(1), table.0[0b11]);
+        assert_eq!(Some(5), table.0[mask("cdfbe").unwrap() as usize]);
+        assert_eq!(Some(3), table.0[mask("fbcad").unwrap() as usize]);
+        assert_eq!(None, table.0[0b101]);
+    }
+
+    #[test]
+    fn test_decode_all_errors() {
+        let good = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab";
+
+        assert_eq!(Err(DecodeError::MissingOutput), decode_all(&[good]));
+        assert_eq!(
+            Err(DecodeError::UnknownPattern("abh".to_string())),
+            decode_all(&[format!("{} | abh", good)])
+        );
+        assert_eq!(
+            Err(DecodeError::UnknownPattern("ag".to_string())),
+            decode_all(&[format!("{} | ag", good)])
+        );
+        // only nine digits
+        assert_eq!(
+            Err(DecodeError::Inconsistent),
+            decode_all(&["acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb ab | ab"])
+        );
+        // an eleventh pattern
+        assert_eq!(
+            Err(DecodeError::Inconsistent),
+            decode_all(&[format!("{} bc | ab", good)])
+        );
+        // two patterns that both look like a 3
+        assert_eq!(
+            Err(DecodeError::Inconsistent),
+            decode_all(&["acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb abcde ab | ab"])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let display = Display::seven_segment();
+
+        assert_eq!(
+            Err(DecodeError::MissingOutput),
+            decode_line(&display, "ab abc")
+        );
+        assert_eq!(
+            Err(DecodeError::TooManyWires(8)),
+            decode_line(&display, "abcdefgh | ab")
+        );
+        // no digit lights up four segments other than 4, so two patterns can't both be it
+        assert_eq!(
+            Err(DecodeError::Inconsistent),
+            decode_line(&display, "abcd abce | ab")
+        );
+        // a 1 on its own could be wired up either way round, and so on for the other segments
+        match decode_line(&display, "ab | ab") {
+            Err(DecodeError::Ambiguous(a, b)) => {
+                assert_ne!(a, b);
+                assert_eq!(Ok(1), a.light(&display, "ab"));
+                assert_eq!(Ok(1), b.light(&display, "ab"));
+            }
+            r => panic!("expected ambiguity, got {:?}", r),
+        }
+        // the output has to be digits too, and no digit lights up just two segments but 1
+        assert_eq!(
+            Err(DecodeError::Inconsistent),
+            decode_line(
+                &display,
+                "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | ag"
+            )
+        );
+    }
+
+    #[test]
+    fn test_custom_display() {
+        // a three segment display, top, middle and bottom, with digits for each pair and all three
+        let display = Display::new(&["TM", "MB", "TB", "TMB"]);
+        let (signals, _) = parse_line("xz yz xy xyz | ").unwrap();
+        let mapping = solve(&display, &signals);
+        // every pair of wires is a digit, so swapping them round still fits
+        assert!(matches!(mapping, Err(DecodeError::Ambiguous(a, b)) if a != b));
+
+        // sixteen segments with almost nothing seen would have billions of wirings to list
+        let display = Display::new(&["AB", "ABCDEFGHIJKLMNOP", "CDEFGHIJKLMNOP"]);
+        assert!(matches!(
+            solve(&display, &["ab"]),
+            Err(DecodeError::Ambiguous(..))
+        ));
+
+        // with one, two and three segments lit, there's only one way round
+        let display = Display::new(&["T", "TM", "TMB"]);
+        let mapping = solve(&display, &["xz", "x", "xyz"]).unwrap();
+        assert_eq!(
+            Mapping(HashMap::from([('x', 'T'), ('z', 'M'), ('y', 'B')])),
+            mapping
+        );
+        assert_eq!(Ok(2), mapping.light(&display, "zyx"));
+        assert_eq!(
+            Err(DecodeError::UnknownPattern("y".to_string())),
+            mapping.light(&display, "y")
+        );
+    }
+}
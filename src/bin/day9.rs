@@ -3,6 +3,31 @@ use aoc2021::lines_as_vec;
 use std::collections::HashMap;
 
 fn main() {
+    // `day9 map` draws the basins, and `day9 colour` draws them in colour
+    let colour = match std::env::args().nth(1).as_deref() {
+        Some("map") => Some(false),
+        Some("colour") => Some(true),
+        _ => None,
+    };
+    if let Some(colour) = colour {
+        let map = lines_to_map(&lines_as_vec("input/day9.txt"));
+        let basins = find_basins(&map);
+        print!(
+            "{}",
+            BasinPrinter {
+                heights: &map,
+                basins: &basins,
+                colour
+            }
+        );
+        for b in basins.iter() {
+            if let Err(e) = b.low_point() {
+                eprintln!("{}", e);
+            }
+        }
+        return;
+    }
+
     println!("Part 1: {}", part1());
     println!("Part 2: {}", part2());
 }
@@ -17,16 +42,13 @@ fn part1() -> u32 {
 
 fn part2() -> usize {
     let map = lines_to_map(&lines_as_vec("input/day9.txt"));
-    let tmap = tag_basin(&map);
-
-    let bsz = count_basin_sz(&tmap);
+    let basins = find_basins(&map);
 
-    let mut v: Vec<usize> = bsz.values().cloned().collect();
+    let mut v: Vec<usize> = basins.iter().map(Basin::size).collect();
     v.sort_unstable();
     v.into_iter()
         .rev()
         .take(3)
-        .into_iter()
         .reduce(|acc, v| acc * v)
         .unwrap()
 }
@@ -72,12 +94,6 @@ enum BasinTag {
     Ridge,
 }
 
-fn find_next(map: &HashMap<(isize, isize), BasinTag>) -> Option<(isize, isize)> {
-    map.iter()
-        .find(|&(_, &c)| c == BasinTag::Unknown)
-        .map(|(&(x, y), _)| (x, y))
-}
-
 fn tag_basin(map: &HashMap<(isize, isize), char>) -> HashMap<(isize, isize), BasinTag> {
     let mut bmap: HashMap<(isize, isize), BasinTag> = map
         .iter()
@@ -92,9 +108,16 @@ fn tag_basin(map: &HashMap<(isize, isize), char>) -> HashMap<(isize, isize), Bas
         })
         .collect();
 
+    // go through the map once, in reading order, filling each basin as we come to it
+    let mut cells: Vec<(isize, isize)> = bmap.keys().copied().collect();
+    cells.sort_unstable_by_key(|&(x, y)| (y, x));
+
     let mut color = 0;
 
-    while let Some(node) = find_next(&bmap) {
+    for node in cells {
+        if bmap[&node] != BasinTag::Unknown {
+            continue;
+        }
         let mut fill_stack: Vec<(isize, isize)> = vec![node];
         while let Some(n) = fill_stack.pop() {
             match bmap.get(&n) {
@@ -119,17 +142,137 @@ fn tag_basin(map: &HashMap<(isize, isize), char>) -> HashMap<(isize, isize), Bas
     bmap
 }
 
-fn count_basin_sz(map: &HashMap<(isize, isize), BasinTag>) -> HashMap<usize, usize> {
-    map.iter().fold(HashMap::new(), |mut acc, (_, t)| match t {
-        BasinTag::Basin(c) => {
-            acc.entry(*c).and_modify(|c| *c += 1).or_insert(1);
-            acc
+/// A basin, and the cells in it
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Basin {
+    id: usize,
+    /// Every cell lower than all its neighbours, which should be just the one
+    lows: Vec<(isize, isize)>,
+    /// In reading order
+    members: Vec<(isize, isize)>,
+}
+
+/// A basin that doesn't drain to a single low point
+#[derive(Debug, Eq, PartialEq)]
+enum BasinError {
+    /// Nowhere in the basin is lower than all its neighbours, so it has a flat bottom
+    NoLowPoint(usize),
+    /// The basin drains to more than one place
+    ManyLowPoints(usize, Vec<(isize, isize)>),
+}
+
+impl std::fmt::Display for BasinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            BasinError::NoLowPoint(id) => write!(f, "basin {} has no low point", id),
+            BasinError::ManyLowPoints(id, lows) => {
+                write!(f, "basin {} has {} low points: {:?}", id, lows.len(), lows)
+            }
+        }
+    }
+}
+
+impl Basin {
+    fn size(&self) -> usize {
+        self.members.len()
+    }
+
+    /// The one place the basin drains to
+    fn low_point(&self) -> Result<(isize, isize), BasinError> {
+        match self.lows[..] {
+            [low] => Ok(low),
+            [] => Err(BasinError::NoLowPoint(self.id)),
+            _ => Err(BasinError::ManyLowPoints(self.id, self.lows.clone())),
+        }
+    }
+}
+
+/// Find every basin, numbered in the order their first cells come in reading order
+fn find_basins(map: &HashMap<(isize, isize), char>) -> Vec<Basin> {
+    let tmap = tag_basin(map);
+    let mut basins: Vec<Basin> = Vec::new();
+
+    let mut cells: Vec<((isize, isize), usize)> = tmap
+        .iter()
+        .filter_map(|(&xy, t)| match t {
+            BasinTag::Basin(c) => Some((xy, *c)),
+            BasinTag::Unknown => panic!("bad map"),
+            BasinTag::Ridge => None,
+        })
+        .collect();
+    cells.sort_unstable_by_key(|&((x, y), _)| (y, x));
+
+    for ((x, y), c) in cells {
+        if c == basins.len() {
+            basins.push(Basin {
+                id: c,
+                lows: Vec::new(),
+                members: Vec::new(),
+            });
+        }
+        basins[c].members.push((x, y));
+        if is_low(map, x, y) {
+            basins[c].lows.push((x, y));
+        }
+    }
+
+    basins
+}
+
+/// Draws each basin with its own letter, ridges as `#`, and low points as `*`
+///
+/// Letters get reused once there are more than 52 basins. In colour, each basin gets its own
+/// background as well, which makes neighbouring basins sharing a letter easier to tell apart.
+struct BasinPrinter<'a> {
+    heights: &'a HashMap<(isize, isize), char>,
+    basins: &'a [Basin],
+    colour: bool,
+}
+
+impl std::fmt::Display for BasinPrinter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const LABELS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        // the 6x6x6 colour cube of the 256 colour palette, skipping the darkest shades
+        const PALETTE: std::ops::Range<usize> = 52..232;
+
+        let mut tags: HashMap<(isize, isize), (usize, bool)> = HashMap::new();
+        for b in self.basins {
+            for &xy in b.members.iter() {
+                tags.insert(xy, (b.id, b.lows.contains(&xy)));
+            }
         }
-        BasinTag::Unknown => {
-            panic!("bad map");
+
+        let (x_max, y_max) = self
+            .heights
+            .keys()
+            .fold((-1, -1), |acc, &(x, y)| (x.max(acc.0), y.max(acc.1)));
+        for y in 0..=y_max {
+            for x in 0..=x_max {
+                let (c, bg) = match tags.get(&(x, y)) {
+                    Some(&(id, low)) => {
+                        let c = if low {
+                            '*'
+                        } else {
+                            LABELS[id % LABELS.len()] as char
+                        };
+                        (c, PALETTE.start + id * 7 % PALETTE.len())
+                    }
+                    None if self.heights.contains_key(&(x, y)) => ('#', 0),
+                    None => (' ', 0),
+                };
+                if self.colour {
+                    write!(f, "\x1b[48;5;{}m{}", bg, c)?;
+                } else {
+                    write!(f, "{}", c)?;
+                }
+            }
+            if self.colour {
+                write!(f, "\x1b[0m")?;
+            }
+            writeln!(f)?;
         }
-        BasinTag::Ridge => acc,
-    })
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -158,15 +301,15 @@ mod day9_tests {
         assert_eq!(*map.get(&(1, 0)).unwrap(), '1');
     }
 
-    #[test]
-    fn test_case_pt1() {
-        let test_data = str_as_vec(
-            "2199943210
+    const TEST_DATA: &str = "2199943210
 3987894921
 9856789892
 8767896789
-9899965678",
-        );
+9899965678";
+
+    #[test]
+    fn test_case_pt1() {
+        let test_data = str_as_vec(TEST_DATA);
 
         let map = lines_to_map(&test_data);
 
@@ -181,31 +324,72 @@ mod day9_tests {
 
     #[test]
     fn test_case_pt2() {
-        let test_data = str_as_vec(
-            "2199943210
-3987894921
-9856789892
-8767896789
-9899965678",
-        );
+        let test_data = str_as_vec(TEST_DATA);
 
         let map = lines_to_map(&test_data);
-        println!("{:?}", map);
-        let tmap = tag_basin(&map);
-        println!("{:?}", tmap);
-
-        let bsz = count_basin_sz(&tmap);
-        println!("{:?}", bsz);
+        let basins = find_basins(&map);
 
-        let mut v: Vec<usize> = bsz.values().cloned().collect();
+        let mut v: Vec<usize> = basins.iter().map(Basin::size).collect();
         v.sort_unstable();
         let p: usize = v
             .into_iter()
             .rev()
             .take(3)
-            .into_iter()
             .reduce(|acc, v| acc * v)
             .unwrap();
         assert_eq!(p, 1134);
     }
+
+    #[test]
+    fn test_basins() {
+        let map = lines_to_map(&str_as_vec(TEST_DATA));
+        let basins = find_basins(&map);
+
+        assert_eq!(
+            vec![3, 9, 14, 9],
+            basins.iter().map(Basin::size).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Ok(vec![(1, 0), (9, 0), (2, 2), (6, 4)]),
+            basins.iter().map(Basin::low_point).collect()
+        );
+        assert_eq!(vec![(0, 0), (1, 0), (0, 1)], basins[0].members);
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            basins.iter().map(|b| b.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_low_point_check() {
+        // a flat bottom has no low point, and a ridge with a gap joins two
+        let map = lines_to_map(&str_as_vec("119\n999\n020\n"));
+        let basins = find_basins(&map);
+
+        assert_eq!(Err(BasinError::NoLowPoint(0)), basins[0].low_point());
+        assert_eq!(
+            Err(BasinError::ManyLowPoints(1, vec![(0, 2), (2, 2)])),
+            basins[1].low_point()
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let map = lines_to_map(&str_as_vec(TEST_DATA));
+        let basins = find_basins(&map);
+        let printer = BasinPrinter {
+            heights: &map,
+            basins: &basins,
+            colour: false,
+        };
+
+        assert_eq!(
+            "a*###bbbb*\n\
+             a#ccc#b#bb\n\
+             #c*ccc#d#b\n\
+             ccccc#ddd#\n\
+             #c###d*ddd\n",
+            printer.to_string()
+        );
+    }
 }
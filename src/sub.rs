@@ -1,45 +1,136 @@
 /// Possible moves your sub can make
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Nav {
     /// Sub moves forward
     Fore(u64),
+    /// Sub moves backward
+    Back(u64),
     /// Sub aims up (depth decreases)
     Up(u64),
     /// Sub aims down (depth increases)
     Down(u64),
 }
 
+impl std::fmt::Display for Nav {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Nav::Fore(d) => write!(f, "forward {}", d),
+            Nav::Back(d) => write!(f, "back {}", d),
+            Nav::Up(d) => write!(f, "up {}", d),
+            Nav::Down(d) => write!(f, "down {}", d),
+        }
+    }
+}
+
 /// Errors resulting from attempts to parse nav input
 #[derive(Debug, PartialEq, Eq)]
-pub struct NavParseError;
+pub enum NavParseError {
+    /// There was nothing to parse
+    Empty,
+    /// The move isn't one we know
+    UnknownVerb(String),
+    /// The move wasn't followed by a distance
+    MissingDistance,
+    /// There was more after the distance
+    ExtraToken(String),
+    /// The distance isn't a number we can use
+    BadNumber(String),
+}
+
+impl std::fmt::Display for NavParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            NavParseError::Empty => write!(f, "no command"),
+            NavParseError::UnknownVerb(v) => write!(f, "unknown command `{}`", v),
+            NavParseError::MissingDistance => write!(f, "missing distance"),
+            NavParseError::ExtraToken(t) => write!(f, "unexpected `{}` after distance", t),
+            NavParseError::BadNumber(n) => write!(f, "bad distance `{}`", n),
+        }
+    }
+}
 
 impl TryFrom<&str> for Nav {
     type Error = NavParseError;
 
     fn try_from(line: &str) -> Result<Self, Self::Error> {
-        if let [f, d] = &(line.split(' ').collect::<Vec<&str>>()).as_slice() {
-            let dist = d.parse::<u64>().map_err(|_| NavParseError {})?;
-            match *f {
-                "forward" => Ok(Nav::Fore(dist)),
-                "down" => Ok(Nav::Down(dist)),
-                "up" => Ok(Nav::Up(dist)),
-                _ => Err(NavParseError {}),
-            }
-        } else {
-            Err(NavParseError {})
+        let mut words = line.split_whitespace();
+        let verb = words.next().ok_or(NavParseError::Empty)?;
+        let make: fn(u64) -> Nav = match verb.to_ascii_lowercase().as_str() {
+            "forward" => Nav::Fore,
+            "back" => Nav::Back,
+            "down" => Nav::Down,
+            "up" => Nav::Up,
+            _ => return Err(NavParseError::UnknownVerb(verb.to_string())),
+        };
+        let d = words.next().ok_or(NavParseError::MissingDistance)?;
+        let dist = d
+            .parse::<u64>()
+            .map_err(|_| NavParseError::BadNumber(d.to_string()))?;
+        if let Some(extra) = words.next() {
+            return Err(NavParseError::ExtraToken(extra.to_string()));
         }
+        Ok(make(dist))
+    }
+}
+
+/// An error parsing a log of nav commands, and the line it was on (counting from 1)
+#[derive(Debug, PartialEq, Eq)]
+pub struct NavLogError {
+    pub line: usize,
+    pub error: NavParseError,
+}
+
+impl std::fmt::Display for NavLogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "line {}: {}", self.line, self.error)
     }
 }
 
+/// Parse a log of nav commands, one per line. Blank lines are skipped.
+pub fn parse_navs<S: AsRef<str>>(lines: &[S]) -> Result<Vec<Nav>, NavLogError> {
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !l.as_ref().trim().is_empty())
+        .map(|(i, l)| Nav::try_from(l.as_ref()).map_err(|error| NavLogError { line: i + 1, error }))
+        .collect()
+}
+
+/// The ways a sub can interpret its moves
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NavModel {
+    /// Up and down change depth directly, and forward only moves forward
+    Direct,
+    /// Up and down change aim, and forward moves along it
+    #[default]
+    Aim,
+}
+
+/// What happens when a move would take a sub above the surface
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Surface {
+    /// Subs cannot fly: the move fails
+    #[default]
+    Error,
+    /// The sub bobs at the surface, and depth stays at zero
+    Clamp,
+    /// The sub takes off, and depth goes negative
+    Fly,
+}
+
 /// A representation of the state of your submarine
-#[derive(Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Sub {
     /// How far forward the sub has travelled
     pub dist: u64,
-    /// How deep the sub is (deeper -> higher value)
-    pub depth: u64,
+    /// How deep the sub is (deeper -> higher value, above the surface -> negative)
+    pub depth: i64,
     /// The amount of incline the sub has (neg -> pointed up, pos -> pointed down)
     pub aim: i64,
+    /// How the sub interprets its moves
+    pub model: NavModel,
+    /// What the sub does at the surface
+    pub surface: Surface,
 }
 
 /// Errors relating to submarines
@@ -52,33 +143,58 @@ pub enum SubError {
 }
 
 impl Sub {
+    /// A sub at the surface, moving according to `model`
+    pub fn new(model: NavModel) -> Self {
+        Sub {
+            model,
+            ..Sub::default()
+        }
+    }
+
     pub fn try_move(&mut self, m: Nav) -> Result<(), SubError> {
+        match self.model {
+            NavModel::Direct => self.try_move_direct(m),
+            NavModel::Aim => self.try_move_aim(m),
+        }
+    }
+
+    fn try_move_direct(&mut self, m: Nav) -> Result<(), SubError> {
+        match m {
+            Nav::Fore(d) => {
+                self.dist = self.dist.checked_add(d).ok_or(SubError::Nav(m))?;
+            }
+            Nav::Back(d) => {
+                self.dist = self.dist.checked_sub(d).ok_or(SubError::Nav(m))?;
+            }
+            Nav::Up(d) => {
+                let adj = i64::try_from(d).map_err(|_| SubError::Overflow(d))?;
+                let depth = self.depth.checked_sub(adj).ok_or(SubError::Overflow(d))?;
+                self.dive_to(depth, m)?;
+            }
+            Nav::Down(d) => {
+                let adj = i64::try_from(d).map_err(|_| SubError::Overflow(d))?;
+                let depth = self.depth.checked_add(adj).ok_or(SubError::Overflow(d))?;
+                self.dive_to(depth, m)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn try_move_aim(&mut self, m: Nav) -> Result<(), SubError> {
         match m {
             Nav::Fore(d) => {
                 // move forward
-                self.dist = match self.dist.checked_add(d) {
-                    None => return Err(SubError::Nav(m)),
-                    Some(dist) => dist,
-                };
+                self.dist = self.dist.checked_add(d).ok_or(SubError::Nav(m))?;
 
                 // move up or down
-                let depth_adj: i64 =
-                    self.aim * i64::try_from(d).map_err(|_| SubError::Overflow(d))?;
-                if depth_adj > 0 {
-                    self.depth = self
-                        .depth
-                        .checked_add(
-                            depth_adj as u64, // positive i64 -> u64 cannot fail
-                        )
-                        .ok_or(SubError::Overflow(d))?;
-                } else {
-                    self.depth = self
-                        .depth
-                        .checked_sub(
-                            depth_adj.abs() as u64, // abs of i64 -> u64 cannot fail
-                        )
-                        .ok_or(SubError::Nav(m))?;
-                }
+                self.follow_aim(d, 1, m)?;
+            }
+            Nav::Back(d) => {
+                // move backward
+                self.dist = self.dist.checked_sub(d).ok_or(SubError::Nav(m))?;
+
+                // back up along the aim, the opposite way to moving forward
+                self.follow_aim(d, -1, m)?;
             }
             Nav::Up(d) => {
                 let adj = d.try_into().map_err(|_| SubError::Overflow(d))?;
@@ -91,6 +207,28 @@ impl Sub {
         }
         Ok(())
     }
+
+    /// Change depth by `aim * d`, going the way the sub is pointed if `dir` is 1, or the opposite
+    /// way if it's -1
+    fn follow_aim(&mut self, d: u64, dir: i64, m: Nav) -> Result<(), SubError> {
+        let depth = i64::try_from(d)
+            .ok()
+            .and_then(|d| d.checked_mul(self.aim))
+            .and_then(|adj| adj.checked_mul(dir))
+            .and_then(|adj| self.depth.checked_add(adj))
+            .ok_or(SubError::Overflow(d))?;
+        self.dive_to(depth, m)
+    }
+
+    /// Go to `depth`, doing whatever `surface` says if that's above the surface
+    fn dive_to(&mut self, depth: i64, m: Nav) -> Result<(), SubError> {
+        self.depth = match self.surface {
+            Surface::Error if depth < 0 => return Err(SubError::Nav(m)),
+            Surface::Clamp => depth.max(0),
+            _ => depth,
+        };
+        Ok(())
+    }
 }
 
 impl std::fmt::Display for Sub {
@@ -99,6 +237,78 @@ impl std::fmt::Display for Sub {
     }
 }
 
+/// A record of a sub's moves, and of its state after each one
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Course {
+    /// The moves made so far, in order
+    navs: Vec<Nav>,
+    /// Where the sub started, followed by where it was after each move
+    states: Vec<Sub>,
+}
+
+impl Course {
+    /// Start a course from `sub`'s current state
+    pub fn new(sub: Sub) -> Self {
+        Course {
+            navs: Vec::new(),
+            states: vec![sub],
+        }
+    }
+
+    /// Move the sub and record where it ends up. A failed move is not recorded.
+    pub fn try_move(&mut self, m: Nav) -> Result<(), SubError> {
+        let mut next = self.current().clone();
+        next.try_move(m)?;
+        self.navs.push(m);
+        self.states.push(next);
+        Ok(())
+    }
+
+    /// Make every move in turn, stopping at the first one that fails
+    pub fn try_moves(&mut self, moves: &[Nav]) -> Result<(), SubError> {
+        moves.iter().try_for_each(|&m| self.try_move(m))
+    }
+
+    pub fn navs(&self) -> &[Nav] {
+        &self.navs
+    }
+
+    pub fn states(&self) -> &[Sub] {
+        &self.states
+    }
+
+    /// Where the sub is now
+    pub fn current(&self) -> &Sub {
+        self.states.last().unwrap() // there is always at least the starting state
+    }
+
+    /// The deepest the sub has been
+    pub fn max_depth(&self) -> i64 {
+        self.states.iter().map(|s| s.depth).max().unwrap()
+    }
+
+    /// The index of the first move that brought the sub back up to (or past) the surface, if any
+    /// did
+    pub fn first_surfaced(&self) -> Option<usize> {
+        self.states
+            .windows(2)
+            .position(|w| w[0].depth > 0 && w[1].depth <= 0)
+    }
+
+    /// Dump the course as CSV, one row per state, starting with where the sub began
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,nav,dist,depth,aim\n");
+        for (i, s) in self.states.iter().enumerate() {
+            let nav = match i {
+                0 => String::new(),
+                i => self.navs[i - 1].to_string(),
+            };
+            csv.push_str(&format!("{},{},{},{},{}\n", i, nav, s.dist, s.depth, s.aim));
+        }
+        csv
+    }
+}
+
 #[cfg(test)]
 mod sub_tests {
 
@@ -113,6 +323,8 @@ mod sub_tests {
                 dist: 1,
                 depth: 0,
                 aim: 0,
+                model: NavModel::Aim,
+                surface: Surface::Error,
             },
             s
         );
@@ -124,6 +336,8 @@ mod sub_tests {
             dist: 5,
             depth: 0,
             aim: 0,
+            model: NavModel::Aim,
+            surface: Surface::Error,
         };
         s.try_move(Nav::Down(5)).unwrap();
         s.try_move(Nav::Fore(8)).unwrap();
@@ -132,6 +346,8 @@ mod sub_tests {
                 dist: 13,
                 depth: 40,
                 aim: 5,
+                model: NavModel::Aim,
+                surface: Surface::Error,
             },
             s
         );
@@ -143,8 +359,172 @@ mod sub_tests {
             dist: 0,
             depth: 0,
             aim: 0,
+            model: NavModel::Aim,
+            surface: Surface::Error,
         };
         s.try_move(Nav::Up(1)).unwrap();
         assert_eq!(Err(SubError::Nav(Nav::Fore(1))), s.try_move(Nav::Fore(1)));
+
+        let mut s = Sub::new(NavModel::Direct);
+        assert_eq!(Err(SubError::Nav(Nav::Up(1))), s.try_move(Nav::Up(1)));
+    }
+
+    #[test]
+    fn test_move_sub_direct() {
+        let mut s = Sub::new(NavModel::Direct);
+        s.try_move(Nav::Down(5)).unwrap();
+        s.try_move(Nav::Fore(8)).unwrap();
+        s.try_move(Nav::Up(3)).unwrap();
+        assert_eq!(
+            Sub {
+                dist: 8,
+                depth: 2,
+                aim: 0,
+                model: NavModel::Direct,
+                surface: Surface::Error,
+            },
+            s
+        );
+    }
+
+    #[test]
+    fn test_course() {
+        let mut c = Course::new(Sub::default());
+        c.try_moves(&[
+            Nav::Down(2),
+            Nav::Fore(3),
+            Nav::Up(4),
+            Nav::Fore(2),
+            Nav::Fore(1),
+        ])
+        .unwrap();
+        assert_eq!(6, c.states().len());
+        assert_eq!(6, c.max_depth());
+        assert_eq!(Some(4), c.first_surfaced());
+        assert_eq!(
+            "\
+step,nav,dist,depth,aim
+0,,0,0,0
+1,down 2,0,0,2
+2,forward 3,3,6,2
+3,up 4,3,6,-2
+4,forward 2,5,2,-2
+5,forward 1,6,0,-2
+",
+            c.to_csv()
+        );
+
+        // going any higher would fly, so the course stops where it was
+        assert_eq!(Err(SubError::Nav(Nav::Fore(1))), c.try_move(Nav::Fore(1)));
+        assert_eq!(5, c.navs().len());
+        assert_eq!(6, c.current().dist);
+    }
+
+    #[test]
+    fn test_parse_nav() {
+        assert_eq!(Ok(Nav::Fore(5)), Nav::try_from("forward 5"));
+        assert_eq!(Ok(Nav::Back(2)), Nav::try_from("  BACK\t 2 "));
+        assert_eq!(Ok(Nav::Up(3)), Nav::try_from("Up 3"));
+        assert_eq!(Err(NavParseError::Empty), Nav::try_from("   "));
+        assert_eq!(
+            Err(NavParseError::UnknownVerb(String::from("sideways"))),
+            Nav::try_from("sideways 3")
+        );
+        assert_eq!(Err(NavParseError::MissingDistance), Nav::try_from("down"));
+        assert_eq!(
+            Err(NavParseError::ExtraToken(String::from("metres"))),
+            Nav::try_from("down 3 metres")
+        );
+        assert_eq!(
+            Err(NavParseError::BadNumber(String::from("-3"))),
+            Nav::try_from("down -3")
+        );
+    }
+
+    #[test]
+    fn test_nav_round_trip() {
+        for n in [Nav::Fore(5), Nav::Back(0), Nav::Up(3), Nav::Down(u64::MAX)] {
+            assert_eq!(Ok(n), Nav::try_from(n.to_string().as_str()));
+        }
+    }
+
+    #[test]
+    fn test_parse_navs() {
+        assert_eq!(
+            Ok(vec![Nav::Fore(1), Nav::Down(2)]),
+            parse_navs(&["forward 1", "", "down 2"])
+        );
+        let err = parse_navs(&["forward 1", "", "dwon 2"]).unwrap_err();
+        assert_eq!(
+            NavLogError {
+                line: 3,
+                error: NavParseError::UnknownVerb(String::from("dwon"))
+            },
+            err
+        );
+        assert_eq!("line 3: unknown command `dwon`", err.to_string());
+    }
+
+    #[test]
+    fn test_move_sub_back() {
+        let mut s = Sub::default();
+        s.try_move(Nav::Down(2)).unwrap();
+        s.try_move(Nav::Fore(5)).unwrap();
+        s.try_move(Nav::Back(3)).unwrap();
+        assert_eq!((2, 4), (s.dist, s.depth));
+        assert_eq!(Err(SubError::Nav(Nav::Back(3))), s.try_move(Nav::Back(3)));
+
+        let mut s = Sub::new(NavModel::Direct);
+        s.try_move(Nav::Fore(5)).unwrap();
+        s.try_move(Nav::Back(3)).unwrap();
+        assert_eq!(2, s.dist);
+    }
+
+    #[test]
+    fn test_surface() {
+        let navs = [Nav::Down(2), Nav::Up(5), Nav::Down(1)];
+
+        let mut s = Sub::new(NavModel::Direct);
+        assert_eq!(
+            Err(SubError::Nav(Nav::Up(5))),
+            navs.iter().try_for_each(|&m| s.try_move(m))
+        );
+        assert_eq!(2, s.depth);
+
+        let mut s = Sub {
+            surface: Surface::Clamp,
+            ..Sub::new(NavModel::Direct)
+        };
+        navs.iter().try_for_each(|&m| s.try_move(m)).unwrap();
+        assert_eq!(1, s.depth);
+
+        let mut s = Sub {
+            surface: Surface::Fly,
+            ..Sub::new(NavModel::Direct)
+        };
+        navs.iter().try_for_each(|&m| s.try_move(m)).unwrap();
+        assert_eq!(-2, s.depth);
+    }
+
+    #[test]
+    fn test_flying_course() {
+        let mut c = Course::new(Sub {
+            surface: Surface::Fly,
+            ..Sub::default()
+        });
+        c.try_moves(&[Nav::Down(1), Nav::Fore(2), Nav::Up(3), Nav::Fore(2)])
+            .unwrap();
+        assert_eq!(-2, c.current().depth);
+        assert_eq!(2, c.max_depth());
+        assert_eq!(Some(3), c.first_surfaced());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut s = Sub {
+            aim: i64::MAX,
+            ..Sub::default()
+        };
+        assert_eq!(Err(SubError::Overflow(2)), s.try_move(Nav::Fore(2)));
     }
 }